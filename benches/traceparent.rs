@@ -0,0 +1,52 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use trace_context::TraceContext;
+
+const TRACEPARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01";
+
+/// The allocating parser `extract` used before `from_bytes` existed, kept for comparison.
+fn split_parse(traceparent: &str) -> (u8, u128, u64, u8) {
+    let parts: Vec<&str> = traceparent.split('-').collect();
+    (
+        u8::from_str_radix(parts[0], 16).unwrap(),
+        u128::from_str_radix(parts[1], 16).unwrap(),
+        u64::from_str_radix(parts[2], 16).unwrap(),
+        u8::from_str_radix(parts[3], 16).unwrap(),
+    )
+}
+
+fn parse(c: &mut Criterion) {
+    let mut headers = http::HeaderMap::new();
+    headers.insert("traceparent", TRACEPARENT.parse().unwrap());
+
+    let mut group = c.benchmark_group("parse");
+    group.bench_function("split", |b| b.iter(|| split_parse(black_box(TRACEPARENT))));
+    group.bench_function("from_bytes", |b| {
+        b.iter(|| TraceContext::from_bytes(black_box(TRACEPARENT.as_bytes())).unwrap())
+    });
+    group.bench_function("extract", |b| {
+        b.iter(|| TraceContext::extract(black_box(&headers)).unwrap())
+    });
+    group.finish();
+}
+
+fn format(c: &mut Criterion) {
+    let context = TraceContext::from_bytes(TRACEPARENT.as_bytes()).unwrap();
+
+    let mut group = c.benchmark_group("format");
+    group.bench_function("format_parse", |b| {
+        b.iter(|| {
+            let value: http::header::HeaderValue =
+                format!("{}", black_box(&context)).parse().unwrap();
+            value
+        })
+    });
+    group.bench_function("to_bytes", |b| b.iter(|| black_box(&context).to_bytes()));
+    group.bench_function("inject", |b| {
+        let mut headers = http::HeaderMap::new();
+        b.iter(|| black_box(&context).inject(&mut headers))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse, format);
+criterion_main!(benches);
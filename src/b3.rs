@@ -0,0 +1,493 @@
+use crate::{
+    hex_field, Deferral, Extractor, IdGenerator, Injector, ParseError, Propagator,
+    RandomIdGenerator, SpanId, TraceContext, TraceFlags, TraceId,
+};
+
+const TRACE_ID: &str = "x-b3-traceid";
+const SPAN_ID: &str = "x-b3-spanid";
+const PARENT_SPAN_ID: &str = "x-b3-parentspanid";
+const SAMPLED: &str = "x-b3-sampled";
+const FLAGS: &str = "x-b3-flags";
+const SINGLE: &str = "b3";
+
+/// Which B3 headers [`B3Propagator::inject`](struct.B3Propagator.html#method.inject) writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum B3Encoding {
+    /// The `X-B3-TraceId`, `X-B3-SpanId`, `X-B3-ParentSpanId` and `X-B3-Sampled` or
+    /// `X-B3-Flags` headers.
+    MultiHeader,
+    /// The single `b3` header.
+    SingleHeader,
+    /// Both the multi-header and the single header formats.
+    Both,
+}
+
+/// Converts between [Zipkin B3](https://github.com/openzipkin/b3-propagation) headers and
+/// TraceContext.
+///
+/// ## Examples
+/// ```
+/// use trace_context::{B3Encoding, B3Propagator};
+///
+/// let mut headers = http::HeaderMap::new();
+/// headers.insert("b3", "00f067aa0ba902b7-b7ad6b7169203331-1".parse().unwrap());
+///
+/// let b3 = B3Propagator::new(B3Encoding::Both);
+/// let context = b3.extract(&headers).unwrap().unwrap();
+///
+/// assert_eq!(context.trace_id().to_string(), "000000000000000000f067aa0ba902b7");
+/// assert_eq!(context.parent_id().unwrap().to_string(), "b7ad6b7169203331");
+/// assert!(context.sampled());
+///
+/// // Emit W3C and B3 headers side by side.
+/// let mut output = http::HeaderMap::new();
+/// context.inject(&mut output);
+/// b3.inject(&context, &mut output);
+///
+/// assert_eq!(output.get("x-b3-traceid").unwrap(), "00f067aa0ba902b7");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct B3Propagator {
+    encoding: B3Encoding,
+}
+
+/// The B3 sampling state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sampling {
+    Defer,
+    Deny,
+    Accept,
+    Debug,
+}
+
+impl B3Propagator {
+    /// Create a B3Propagator writing the given encoding.
+    pub fn new(encoding: B3Encoding) -> Self {
+        Self { encoding }
+    }
+
+    /// Create and return a TraceContext based on the B3 headers of the carrier.
+    ///
+    /// Both encodings are accepted regardless of the configured one; the single `b3` header
+    /// takes precedence. Returns `Ok(None)` if the carrier has no B3 headers. A header that only
+    /// carries a sampling decision, like `b3: 0`, results in a new root TraceContext with that
+    /// decision applied. Headers without a sampling decision result in an unsampled
+    /// TraceContext whose decision is
+    /// [deferred](struct.TraceContext.html#method.sampling_deferred).
+    pub fn extract<E: Extractor + ?Sized>(
+        &self,
+        carrier: &E,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        self.extract_with(carrier, &RandomIdGenerator::new())
+    }
+
+    /// Create and return a TraceContext like [`extract`](#method.extract), using `gen` to
+    /// generate its id.
+    pub fn extract_with<E: Extractor + ?Sized, G: IdGenerator + ?Sized>(
+        &self,
+        carrier: &E,
+        gen: &G,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        match carrier.get(SINGLE) {
+            Some(b3) => extract_single(b3, gen).map(Some),
+            None => extract_multi(carrier, gen),
+        }
+    }
+
+    /// Add the B3 headers for the TraceContext to the carrier.
+    ///
+    /// Trace ids whose upper 64 bits are zero are written as 16 hex characters, so services
+    /// only supporting 64-bit trace ids can read them. If the sampling decision is deferred,
+    /// the sampling state is omitted, leaving the decision to the receiver.
+    pub fn inject<I: Injector + ?Sized>(&self, context: &TraceContext, carrier: &mut I) {
+        let trace_id = format_trace_id(context.trace_id());
+
+        if self.encoding != B3Encoding::SingleHeader {
+            carrier.set(TRACE_ID, trace_id.clone());
+            carrier.set(SPAN_ID, context.id().to_string());
+            if let Some(parent_id) = context.parent_id() {
+                carrier.set(PARENT_SPAN_ID, parent_id.to_string());
+            }
+            if context.debug() {
+                carrier.set(FLAGS, "1".to_owned());
+            } else if let Some(sampled) = sampled(context) {
+                carrier.set(SAMPLED, sampled.to_owned());
+            }
+        }
+
+        if self.encoding != B3Encoding::MultiHeader {
+            let sampling = if context.debug() {
+                Some("d")
+            } else {
+                sampled(context)
+            };
+            let mut b3 = format!("{}-{}", trace_id, context.id());
+            // The parent span id can only follow a sampling state.
+            if let Some(sampling) = sampling {
+                b3.push('-');
+                b3.push_str(sampling);
+                if let Some(parent_id) = context.parent_id() {
+                    b3.push('-');
+                    b3.push_str(&parent_id.to_string());
+                }
+            }
+            carrier.set(SINGLE, b3);
+        }
+    }
+}
+
+impl Default for B3Propagator {
+    fn default() -> Self {
+        Self::new(B3Encoding::MultiHeader)
+    }
+}
+
+impl Propagator for B3Propagator {
+    fn name(&self) -> &'static str {
+        "b3"
+    }
+
+    fn extract_with(
+        &self,
+        carrier: &dyn Extractor,
+        gen: &dyn IdGenerator,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        B3Propagator::extract_with(self, carrier, gen)
+    }
+
+    fn inject(&self, context: &TraceContext, carrier: &mut dyn Injector) {
+        B3Propagator::inject(self, context, carrier)
+    }
+}
+
+fn extract_single<G: IdGenerator + ?Sized>(b3: &str, gen: &G) -> Result<TraceContext, ParseError> {
+    let mut parts = b3.split('-');
+    let first = parts.next().unwrap_or_default();
+    let span_id = match parts.next() {
+        Some(span_id) => span_id,
+        None => return Ok(root(gen, parse_sampling(first, true)?)),
+    };
+
+    let trace_id = parse_trace_id(first, "trace-id")?;
+    let span_id = parse_span_id(span_id, "span-id")?;
+    let sampling = match parts.next() {
+        Some(sampling) => parse_sampling(sampling, true)?,
+        None => Sampling::Defer,
+    };
+    if let Some(parent_span_id) = parts.next() {
+        parse_span_id(parent_span_id, "parent-span-id")?;
+    }
+    if parts.next().is_some() {
+        return Err(ParseError::BadLength(SINGLE));
+    }
+
+    Ok(remote(gen, trace_id, span_id, sampling))
+}
+
+fn extract_multi<E: Extractor + ?Sized, G: IdGenerator + ?Sized>(
+    carrier: &E,
+    gen: &G,
+) -> Result<Option<TraceContext>, ParseError> {
+    let mut sampling = match carrier.get(SAMPLED) {
+        Some(sampled) => parse_sampling(sampled, false)?,
+        None => Sampling::Defer,
+    };
+    if carrier.get(FLAGS) == Some("1") {
+        sampling = Sampling::Debug;
+    }
+
+    let (trace_id, span_id) = match (carrier.get(TRACE_ID), carrier.get(SPAN_ID)) {
+        (Some(trace_id), Some(span_id)) => (trace_id, span_id),
+        (Some(_), None) => return Err(ParseError::MissingField("X-B3-SpanId")),
+        (None, Some(_)) => return Err(ParseError::MissingField("X-B3-TraceId")),
+        (None, None) if sampling == Sampling::Defer => return Ok(None),
+        (None, None) => return Ok(Some(root(gen, sampling))),
+    };
+
+    let trace_id = parse_trace_id(trace_id, "X-B3-TraceId")?;
+    let span_id = parse_span_id(span_id, "X-B3-SpanId")?;
+    if let Some(parent_span_id) = carrier.get(PARENT_SPAN_ID) {
+        parse_span_id(parent_span_id, "X-B3-ParentSpanId")?;
+    }
+
+    Ok(Some(remote(gen, trace_id, span_id, sampling)))
+}
+
+/// Parse a 64-bit or 128-bit trace id. 64-bit trace ids are zero-padded to 128 bits.
+fn parse_trace_id(trace_id: &str, name: &'static str) -> Result<TraceId, ParseError> {
+    let len = if trace_id.len() == 16 { 16 } else { 32 };
+    let trace_id = TraceId::from(hex_field(Some(trace_id.as_bytes()), name, len)?);
+    if !trace_id.is_valid() {
+        return Err(ParseError::ZeroTraceId);
+    }
+    Ok(trace_id)
+}
+
+fn parse_span_id(span_id: &str, name: &'static str) -> Result<SpanId, ParseError> {
+    let span_id = SpanId::from(hex_field(Some(span_id.as_bytes()), name, 16)? as u64);
+    if !span_id.is_valid() {
+        return Err(ParseError::ZeroParentId);
+    }
+    Ok(span_id)
+}
+
+fn parse_sampling(sampling: &str, single: bool) -> Result<Sampling, ParseError> {
+    match sampling {
+        "0" => Ok(Sampling::Deny),
+        "1" => Ok(Sampling::Accept),
+        "d" if single => Ok(Sampling::Debug),
+        "false" if !single => Ok(Sampling::Deny),
+        "true" if !single => Ok(Sampling::Accept),
+        _ => Err(ParseError::InvalidSamplingState),
+    }
+}
+
+fn remote<G: IdGenerator + ?Sized>(
+    gen: &G,
+    trace_id: TraceId,
+    span_id: SpanId,
+    sampling: Sampling,
+) -> TraceContext {
+    let mut context = TraceContext::remote(gen, trace_id, span_id, TraceFlags::default());
+    apply(&mut context, sampling);
+    context
+}
+
+fn root<G: IdGenerator + ?Sized>(gen: &G, sampling: Sampling) -> TraceContext {
+    let mut context = TraceContext::new_root_with(gen);
+    apply(&mut context, sampling);
+    context
+}
+
+fn apply(context: &mut TraceContext, sampling: Sampling) {
+    match sampling {
+        Sampling::Defer => context.defer_sampling(Deferral::Absent),
+        Sampling::Deny => context.set_sampled(false),
+        Sampling::Accept => context.set_sampled(true),
+        Sampling::Debug => context.set_debug(true),
+    }
+}
+
+fn format_trace_id(trace_id: TraceId) -> String {
+    let trace_id = u128::from(trace_id);
+    if trace_id >> 64 == 0 {
+        format!("{:016x}", trace_id)
+    } else {
+        format!("{:032x}", trace_id)
+    }
+}
+
+/// Return the sampling state to write, or `None` if the decision is deferred.
+fn sampled(context: &TraceContext) -> Option<&'static str> {
+    if context.sampling_deferred() {
+        None
+    } else if context.sampled() {
+        Some("1")
+    } else {
+        Some("0")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{B3Encoding, B3Propagator};
+    use crate::carrier::test::carrier;
+    use crate::{ParseError, RandomIdGenerator, SpanId, TraceId};
+    use std::collections::HashMap;
+
+    #[test]
+    fn multi_header() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let carrier = carrier(&[
+            ("x-b3-traceid", "80f198ee56343ba864fe8b2a57d3eff7"),
+            ("x-b3-spanid", "e457b5a2e4d86bd1"),
+            ("x-b3-parentspanid", "05e3ac9a4f6e3b90"),
+            ("x-b3-sampled", "1"),
+        ]);
+        let context = B3Propagator::default().extract(&carrier)?.unwrap();
+        assert_eq!(
+            context.trace_id(),
+            "80f198ee56343ba864fe8b2a57d3eff7".parse()?
+        );
+        assert_eq!(context.parent_id(), Some("e457b5a2e4d86bd1".parse()?));
+        assert!(context.sampled());
+        assert!(!context.debug());
+        Ok(())
+    }
+
+    #[test]
+    fn single_header() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let carrier = carrier(&[(
+            "b3",
+            "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-d-05e3ac9a4f6e3b90",
+        )]);
+        let context = B3Propagator::default().extract(&carrier)?.unwrap();
+        assert_eq!(context.parent_id(), Some("e457b5a2e4d86bd1".parse()?));
+        assert!(context.debug());
+        assert!(context.sampled());
+        Ok(())
+    }
+
+    #[test]
+    fn short_trace_id() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let carrier = carrier(&[
+            ("x-b3-traceid", "a3ce929d0e0e4736"),
+            ("x-b3-spanid", "00f067aa0ba902b7"),
+        ]);
+        let context = B3Propagator::default().extract(&carrier)?.unwrap();
+        assert_eq!(context.trace_id(), TraceId::from(0xa3ce929d0e0e4736));
+        assert!(!context.sampled());
+        Ok(())
+    }
+
+    #[test]
+    fn debug_flag() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let carrier = carrier(&[
+            ("x-b3-traceid", "a3ce929d0e0e4736"),
+            ("x-b3-spanid", "00f067aa0ba902b7"),
+            ("x-b3-sampled", "0"),
+            ("x-b3-flags", "1"),
+        ]);
+        let context = B3Propagator::default().extract(&carrier)?.unwrap();
+        assert!(context.debug());
+        assert!(context.sampled());
+        Ok(())
+    }
+
+    #[test]
+    fn deny_sampling() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let context = B3Propagator::default()
+            .extract(&carrier(&[("b3", "0")]))?
+            .unwrap();
+        assert_eq!(context.parent_id(), None);
+        assert!(!context.sampled());
+
+        let context = B3Propagator::default()
+            .extract(&carrier(&[("x-b3-sampled", "0")]))?
+            .unwrap();
+        assert!(!context.sampled());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        let b3 = B3Propagator::default();
+        assert_eq!(
+            b3.extract(&carrier(&[("x-b3-traceid", "a3ce929d0e0e4736")]))
+                .unwrap_err(),
+            ParseError::MissingField("X-B3-SpanId")
+        );
+        assert_eq!(
+            b3.extract(&carrier(&[("b3", "a3ce929d0e0e4736-00f067aa0ba902b7-2")]))
+                .unwrap_err(),
+            ParseError::InvalidSamplingState
+        );
+        assert_eq!(
+            b3.extract(&carrier(&[("b3", "a3ce929d0e0e47-00f067aa0ba902b7")]))
+                .unwrap_err(),
+            ParseError::BadLength("trace-id")
+        );
+        assert_eq!(
+            b3.extract(&carrier(&[("b3", "a3ce929d0e0e4736-0000000000000000")]))
+                .unwrap_err(),
+            ParseError::ZeroParentId
+        );
+    }
+
+    #[test]
+    fn inject() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let parent = crate::TraceContext::from_bytes(
+            b"00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01",
+        )?;
+        let mut carrier = HashMap::new();
+        B3Propagator::new(B3Encoding::Both).inject(&parent, &mut carrier);
+
+        let id = parent.id().to_string();
+        assert_eq!(carrier["x-b3-traceid"], "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(carrier["x-b3-spanid"], id);
+        assert_eq!(carrier["x-b3-parentspanid"], "00f067aa0ba902b7");
+        assert_eq!(carrier["x-b3-sampled"], "1");
+        assert_eq!(
+            carrier["b3"],
+            format!("0af7651916cd43dd8448eb211c80319c-{}-1-00f067aa0ba902b7", id)
+        );
+
+        let child = B3Propagator::default().extract(&carrier)?.unwrap();
+        assert_eq!(child.trace_id(), parent.trace_id());
+        assert_eq!(child.parent_id(), Some(parent.id()));
+        Ok(())
+    }
+
+    #[test]
+    fn inject_deferred() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let carrier = carrier(&[
+            ("x-b3-traceid", "a3ce929d0e0e4736"),
+            ("x-b3-spanid", "00f067aa0ba902b7"),
+        ]);
+        let context = B3Propagator::default().extract(&carrier)?.unwrap();
+        assert!(context.sampling_deferred());
+        assert!(!context.sampled());
+
+        let child = context.child();
+        let mut output = HashMap::new();
+        B3Propagator::new(B3Encoding::Both).inject(&child, &mut output);
+        assert!(!output.contains_key("x-b3-sampled"));
+        assert_eq!(output["b3"], format!("a3ce929d0e0e4736-{}", child.id()));
+
+        let extracted = B3Propagator::new(B3Encoding::Both)
+            .extract(&output)?
+            .unwrap();
+        assert!(extracted.sampling_deferred());
+        let mut output = HashMap::new();
+        B3Propagator::default().inject(&extracted, &mut output);
+        assert!(!output.contains_key("x-b3-sampled"));
+
+        let mut decided = extracted.child();
+        decided.set_sampled(false);
+        let mut output = HashMap::new();
+        B3Propagator::default().inject(&decided, &mut output);
+        assert_eq!(output["x-b3-sampled"], "0");
+        Ok(())
+    }
+
+    #[test]
+    fn drop_debug() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut context = B3Propagator::default()
+            .extract(&carrier(&[("b3", "a3ce929d0e0e4736-00f067aa0ba902b7-d")]))?
+            .unwrap();
+        assert!(context.debug());
+        context.set_sampled(false);
+        assert!(!context.debug());
+
+        let mut output = HashMap::new();
+        B3Propagator::new(B3Encoding::Both).inject(&context, &mut output);
+        assert_eq!(output["x-b3-sampled"], "0");
+        assert!(!output.contains_key("x-b3-flags"));
+        assert_eq!(
+            output["b3"],
+            format!("a3ce929d0e0e4736-{}-0-00f067aa0ba902b7", context.id())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn inject_debug() {
+        let mut context = crate::TraceContext::remote(
+            &RandomIdGenerator::new(),
+            TraceId::from(1),
+            SpanId::from(2),
+            Default::default(),
+        );
+        context.set_debug(true);
+        let mut carrier = HashMap::new();
+        B3Propagator::new(B3Encoding::MultiHeader).inject(&context, &mut carrier);
+        assert_eq!(carrier["x-b3-traceid"], "0000000000000001");
+        assert_eq!(carrier["x-b3-flags"], "1");
+        assert!(!carrier.contains_key("x-b3-sampled"));
+        assert!(!carrier.contains_key("b3"));
+
+        let mut carrier = HashMap::new();
+        B3Propagator::new(B3Encoding::SingleHeader).inject(&context, &mut carrier);
+        assert!(carrier["b3"].ends_with("-d-0000000000000002"));
+        assert!(!carrier.contains_key("x-b3-traceid"));
+    }
+}
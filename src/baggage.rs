@@ -0,0 +1,436 @@
+use crate::{Extractor, Injector, ParseError};
+use std::fmt;
+use std::str::FromStr;
+
+/// The maximum number of list-members allowed in a `baggage` header.
+const MAX_MEMBERS: usize = 64;
+
+/// The maximum length in bytes of a `baggage` header.
+const MAX_LEN: usize = 8192;
+
+/// A property attached to a baggage list-member, either a bare key or a `key=value` pair.
+pub type BaggageProperty = (String, Option<String>);
+
+/// User-defined key-value pairs carried in the W3C `baggage` HTTP header.
+///
+/// Values are stored percent-decoded and encoded again when the header is written.
+///
+/// ## Examples
+/// ```
+/// let mut headers = http::HeaderMap::new();
+/// headers.insert("baggage", "tenant=acme,cohort=beta%20users;ttl=60".parse().unwrap());
+///
+/// let baggage = trace_context::Baggage::extract(&headers).unwrap();
+///
+/// assert_eq!(baggage.get("tenant"), Some("acme"));
+/// assert_eq!(baggage.get("cohort"), Some("beta users"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Baggage {
+    entries: Vec<(String, String, Vec<BaggageProperty>)>,
+}
+
+impl Baggage {
+    /// Create an empty Baggage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create and return a Baggage object based on the `baggage` HTTP header.
+    ///
+    /// Multiple `baggage` headers are combined into a single list. A missing header results in
+    /// an empty Baggage.
+    pub fn extract(headers: &http::HeaderMap) -> Result<Self, ParseError> {
+        let mut values = Vec::new();
+        for value in headers.get_all("baggage") {
+            values.push(
+                value
+                    .to_str()
+                    .map_err(|_| ParseError::InvalidBaggageValue)?,
+            );
+        }
+        values.join(",").parse()
+    }
+
+    /// Create and return a Baggage object based on the `baggage` key of any carrier.
+    pub fn extract_from<E: Extractor + ?Sized>(carrier: &E) -> Result<Self, ParseError> {
+        carrier.get("baggage").unwrap_or_default().parse()
+    }
+
+    /// Add the baggage header to the http headers.
+    ///
+    /// The header is only written if the Baggage isn't empty.
+    ///
+    /// ## Examples
+    /// ```
+    /// let mut baggage = trace_context::Baggage::new();
+    /// baggage.insert("tenant", "acme corp").unwrap();
+    ///
+    /// let mut headers = http::HeaderMap::new();
+    /// baggage.inject(&mut headers);
+    ///
+    /// assert_eq!(headers.get("baggage").unwrap(), "tenant=acme%20corp");
+    /// ```
+    pub fn inject(&self, headers: &mut http::HeaderMap) {
+        if self.is_empty() {
+            headers.remove("baggage");
+        } else {
+            headers.insert("baggage", self.to_string().parse().unwrap());
+        }
+    }
+
+    /// Add the `baggage` key to any carrier, if the Baggage isn't empty.
+    pub fn inject_into<I: Injector + ?Sized>(&self, carrier: &mut I) {
+        if !self.is_empty() {
+            carrier.set("baggage", self.to_string());
+        }
+    }
+
+    /// Returns true if the Baggage doesn't contain any list-members.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Return the number of list-members in the Baggage.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Return the decoded value stored for `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _, _)| k == key)
+            .map(|(_, v, _)| v.as_str())
+    }
+
+    /// Return the properties attached to `key`.
+    pub fn properties(&self, key: &str) -> Option<&[BaggageProperty]> {
+        self.entries
+            .iter()
+            .find(|(k, _, _)| k == key)
+            .map(|(_, _, p)| p.as_slice())
+    }
+
+    /// Iterate over the list-members as `(key, value)` pairs, in header order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|(k, v, _)| (k.as_str(), v.as_str()))
+    }
+
+    /// Insert or update a list-member without properties.
+    ///
+    /// The value may contain any characters; it's percent-encoded when the header is written.
+    pub fn insert(&mut self, key: &str, value: &str) -> Result<(), ParseError> {
+        self.insert_with_properties(key, value, Vec::new())
+    }
+
+    /// Insert or update a list-member together with its properties.
+    ///
+    /// An existing list-member keeps its position. Fails without modifying the Baggage if the
+    /// key is invalid or if the spec limits of 64 list-members and 8192 bytes would be exceeded.
+    pub fn insert_with_properties(
+        &mut self,
+        key: &str,
+        value: &str,
+        properties: Vec<BaggageProperty>,
+    ) -> Result<(), ParseError> {
+        if !valid_key(key) || properties.iter().any(|(k, _)| !valid_key(k)) {
+            return Err(ParseError::InvalidBaggageKey);
+        }
+
+        let mut baggage = self.clone();
+        match baggage.entries.iter_mut().find(|(k, _, _)| k == key) {
+            Some(entry) => {
+                entry.1 = value.to_owned();
+                entry.2 = properties;
+            }
+            None => baggage
+                .entries
+                .push((key.to_owned(), value.to_owned(), properties)),
+        }
+        baggage.check_limits()?;
+
+        *self = baggage;
+        Ok(())
+    }
+
+    /// Remove a list-member, returning its decoded value if it was present.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let index = self.entries.iter().position(|(k, _, _)| k == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    fn check_limits(&self) -> Result<(), ParseError> {
+        if self.entries.len() > MAX_MEMBERS {
+            return Err(ParseError::TooManyBaggageMembers);
+        }
+        if self.to_string().len() > MAX_LEN {
+            return Err(ParseError::BaggageTooLarge);
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Baggage {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() > MAX_LEN {
+            return Err(ParseError::BaggageTooLarge);
+        }
+
+        let mut baggage = Baggage::new();
+
+        for member in s.split(',') {
+            let member = trim_ows(member);
+            if member.is_empty() {
+                continue;
+            }
+
+            let mut parts = member.split(';');
+            let (key, value) = parse_pair(parts.next().unwrap())?;
+            let value = value.ok_or(ParseError::InvalidBaggageValue)?;
+            let properties = parts
+                .map(trim_ows)
+                .filter(|property| !property.is_empty())
+                .map(parse_pair)
+                .collect::<Result<_, _>>()?;
+
+            match baggage.entries.iter_mut().find(|(k, _, _)| *k == key) {
+                Some(entry) => {
+                    entry.1 = value;
+                    entry.2 = properties;
+                }
+                None => baggage.entries.push((key, value, properties)),
+            }
+        }
+
+        if baggage.entries.len() > MAX_MEMBERS {
+            return Err(ParseError::TooManyBaggageMembers);
+        }
+
+        Ok(baggage)
+    }
+}
+
+impl fmt::Display for Baggage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (key, value, properties)) in self.entries.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}={}", key, Encoded(value))?;
+            for (key, value) in properties {
+                match value {
+                    Some(value) => write!(f, ";{}={}", key, Encoded(value))?,
+                    None => write!(f, ";{}", key)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse `key = value` or a bare `key`, decoding the value.
+fn parse_pair(pair: &str) -> Result<(String, Option<String>), ParseError> {
+    let (key, value) = match pair.find('=') {
+        Some(eq) => (trim_ows(&pair[..eq]), Some(trim_ows(&pair[eq + 1..]))),
+        None => (trim_ows(pair), None),
+    };
+    if !valid_key(key) {
+        return Err(ParseError::InvalidBaggageKey);
+    }
+    let value = match value {
+        Some(value) => Some(decode(value)?),
+        None => None,
+    };
+    Ok((key.to_owned(), value))
+}
+
+fn trim_ows(s: &str) -> &str {
+    s.trim_matches(|c| c == ' ' || c == '\t')
+}
+
+/// Returns true if `key` is a valid RFC 7230 token.
+fn valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// Returns true if `b` may appear unencoded in a baggage value.
+fn is_baggage_octet(b: u8) -> bool {
+    matches!(b, 0x21 | 0x23..=0x2b | 0x2d..=0x3a | 0x3c..=0x5b | 0x5d..=0x7e)
+}
+
+/// Percent-decode a baggage value. Invalid UTF-8 is replaced with U+FFFD.
+fn decode(value: &str) -> Result<String, ParseError> {
+    let valid = value.bytes().enumerate().all(|(i, b)| match b {
+        b'%' => value
+            .get(i + 1..i + 3)
+            .is_some_and(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit())),
+        b => is_baggage_octet(b),
+    });
+    if !valid {
+        return Err(ParseError::InvalidBaggageValue);
+    }
+    Ok(percent_decode(value))
+}
+
+/// Decode `%XX` escapes, leaving anything that isn't a valid escape untouched. Invalid UTF-8
+/// is replaced with U+FFFD.
+pub(crate) fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = value
+            .get(i + 1..i + 3)
+            .filter(|hex| bytes[i] == b'%' && hex.bytes().all(|b| b.is_ascii_hexdigit()));
+        match escape {
+            Some(hex) => {
+                decoded.push(u8::from_str_radix(hex, 16).unwrap());
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Percent-encodes a baggage value when formatted.
+pub(crate) struct Encoded<'a>(pub(crate) &'a str);
+
+impl fmt::Display for Encoded<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &b in self.0.as_bytes() {
+            if is_baggage_octet(b) && b != b'%' {
+                write!(f, "{}", b as char)?;
+            } else {
+                write!(f, "%{:02X}", b)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Baggage;
+    use crate::ParseError;
+
+    #[test]
+    fn parse() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let baggage: Baggage = "key1 = value1, key2=caf%C3%A9 ;prop1;prop2 = p%2C2".parse()?;
+        assert_eq!(baggage.len(), 2);
+        assert_eq!(baggage.get("key1"), Some("value1"));
+        assert_eq!(baggage.get("key2"), Some("café"));
+        assert_eq!(
+            baggage.properties("key2").unwrap(),
+            &[
+                ("prop1".to_owned(), None),
+                ("prop2".to_owned(), Some("p,2".to_owned()))
+            ][..]
+        );
+        assert_eq!(
+            baggage.to_string(),
+            "key1=value1,key2=caf%C3%A9;prop1;prop2=p%2C2"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            "key".parse::<Baggage>().unwrap_err(),
+            ParseError::InvalidBaggageValue
+        );
+        assert_eq!(
+            "k(y=1".parse::<Baggage>().unwrap_err(),
+            ParseError::InvalidBaggageKey
+        );
+        assert_eq!(
+            "key=a b".parse::<Baggage>().unwrap_err(),
+            ParseError::InvalidBaggageValue
+        );
+        assert_eq!(
+            "key=%zz".parse::<Baggage>().unwrap_err(),
+            ParseError::InvalidBaggageValue
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let members: Vec<String> = (0..65).map(|i| format!("k{}=v", i)).collect();
+        assert_eq!(
+            members.join(",").parse::<Baggage>().unwrap_err(),
+            ParseError::TooManyBaggageMembers
+        );
+        assert_eq!(
+            format!("k={}", "v".repeat(8192))
+                .parse::<Baggage>()
+                .unwrap_err(),
+            ParseError::BaggageTooLarge
+        );
+
+        let mut baggage = Baggage::new();
+        assert_eq!(
+            baggage.insert("k", &"v".repeat(8192)).unwrap_err(),
+            ParseError::BaggageTooLarge
+        );
+        for i in 0..64 {
+            baggage.insert(&format!("k{}", i), "v").unwrap();
+        }
+        assert_eq!(
+            baggage.insert("k64", "v").unwrap_err(),
+            ParseError::TooManyBaggageMembers
+        );
+        assert_eq!(baggage.len(), 64);
+    }
+
+    #[test]
+    fn insert_and_remove() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut baggage: Baggage = "tenant=acme,cohort=beta".parse()?;
+        baggage.insert("tenant", "globex")?;
+        baggage.insert("region", "eu, west")?;
+        assert_eq!(
+            baggage.to_string(),
+            "tenant=globex,cohort=beta,region=eu%2C%20west"
+        );
+        assert_eq!(baggage.remove("cohort"), Some("beta".to_owned()));
+        assert_eq!(baggage.remove("cohort"), None);
+        assert_eq!(
+            baggage.insert("bad key", "1").unwrap_err(),
+            ParseError::InvalidBaggageKey
+        );
+        let members: Vec<(&str, &str)> = baggage.iter().collect();
+        assert_eq!(members, vec![("tenant", "globex"), ("region", "eu, west")]);
+        Ok(())
+    }
+
+    #[test]
+    fn extract_and_inject() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("baggage", "tenant=acme".parse()?);
+        headers.append("baggage", "cohort=beta".parse()?);
+        let baggage = Baggage::extract(&headers)?;
+        assert_eq!(baggage.len(), 2);
+
+        let mut output = http::HeaderMap::new();
+        baggage.inject(&mut output);
+        assert_eq!(output.get("baggage").unwrap(), "tenant=acme,cohort=beta");
+
+        let mut carrier = std::collections::HashMap::new();
+        baggage.inject_into(&mut carrier);
+        assert_eq!(Baggage::extract_from(&carrier)?, baggage);
+
+        assert!(Baggage::extract(&http::HeaderMap::new())?.is_empty());
+        Ok(())
+    }
+}
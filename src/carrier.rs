@@ -0,0 +1,139 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
+
+/// A carrier that trace context can be read from, such as HTTP headers or message metadata.
+///
+/// Implement this for your own transport to use it with
+/// [`TraceContext::extract_from`](struct.TraceContext.html#method.extract_from).
+pub trait Extractor {
+    /// Return the value stored for `key`, if any.
+    fn get(&self, key: &str) -> Option<&str>;
+
+    /// Returns true if the carrier has a value for `key`, even one `get` can't return because
+    /// it isn't valid text.
+    fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Return all the keys stored in the carrier.
+    fn keys(&self) -> Vec<&str>;
+}
+
+/// A carrier that trace context can be written to, such as HTTP headers or message metadata.
+///
+/// Implement this for your own transport to use it with
+/// [`TraceContext::inject_into`](struct.TraceContext.html#method.inject_into).
+pub trait Injector {
+    /// Store `value` under `key`, replacing any existing value.
+    fn set(&mut self, key: &str, value: String);
+}
+
+impl Extractor for http::HeaderMap {
+    /// Return the first value of the header named `key`, if it's valid visible ASCII.
+    ///
+    /// Use `contains_key` to tell an invalid value from a missing header.
+    fn get(&self, key: &str) -> Option<&str> {
+        http::HeaderMap::get(self, key).and_then(|value| value.to_str().ok())
+    }
+
+    fn contains_key(&self, key: &str) -> bool {
+        http::HeaderMap::contains_key(self, key)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        http::HeaderMap::keys(self)
+            .map(|name| name.as_str())
+            .collect()
+    }
+}
+
+impl Injector for http::HeaderMap {
+    /// Insert the header, silently skipping names or values that aren't valid in HTTP headers.
+    fn set(&mut self, key: &str, value: String) {
+        let name = http::header::HeaderName::from_bytes(key.as_bytes());
+        let value = http::header::HeaderValue::from_str(&value);
+        if let (Ok(name), Ok(value)) = (name, value) {
+            self.insert(name, value);
+        }
+    }
+}
+
+impl<S: BuildHasher> Extractor for HashMap<String, String, S> {
+    fn get(&self, key: &str) -> Option<&str> {
+        HashMap::get(self, key).map(String::as_str)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        HashMap::keys(self).map(String::as_str).collect()
+    }
+}
+
+impl<S: BuildHasher> Injector for HashMap<String, String, S> {
+    fn set(&mut self, key: &str, value: String) {
+        self.insert(key.to_owned(), value);
+    }
+}
+
+impl Extractor for BTreeMap<String, String> {
+    fn get(&self, key: &str) -> Option<&str> {
+        BTreeMap::get(self, key).map(String::as_str)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        BTreeMap::keys(self).map(String::as_str).collect()
+    }
+}
+
+impl Injector for BTreeMap<String, String> {
+    fn set(&mut self, key: &str, value: String) {
+        self.insert(key.to_owned(), value);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::{Extractor, Injector};
+    use std::collections::{BTreeMap, HashMap};
+
+    /// Create a carrier holding the given `(key, value)` pairs.
+    pub(crate) fn carrier(headers: &[(&str, &str)]) -> HashMap<String, String> {
+        headers
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn header_map() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut headers = http::HeaderMap::new();
+        Injector::set(&mut headers, "traceparent", "00-01".to_owned());
+        Injector::set(&mut headers, "bad header", "value".to_owned());
+        Injector::set(&mut headers, "tracestate", "bad\nvalue".to_owned());
+        assert_eq!(Extractor::get(&headers, "traceparent"), Some("00-01"));
+        assert_eq!(Extractor::keys(&headers), vec!["traceparent"]);
+
+        headers.insert("tracestate", http::HeaderValue::from_bytes(b"\xe9")?);
+        assert_eq!(Extractor::get(&headers, "tracestate"), None);
+        assert!(Extractor::contains_key(&headers, "tracestate"));
+        assert!(!Extractor::contains_key(&headers, "baggage"));
+        Ok(())
+    }
+
+    #[test]
+    fn hash_map() {
+        let mut map = HashMap::new();
+        Injector::set(&mut map, "traceparent", "00-01".to_owned());
+        assert_eq!(Extractor::get(&map, "traceparent"), Some("00-01"));
+        assert_eq!(Extractor::get(&map, "tracestate"), None);
+        assert_eq!(Extractor::keys(&map), vec!["traceparent"]);
+    }
+
+    #[test]
+    fn btree_map() {
+        let mut map = BTreeMap::new();
+        Injector::set(&mut map, "tracestate", "acme=1".to_owned());
+        Injector::set(&mut map, "traceparent", "00-01".to_owned());
+        assert_eq!(Extractor::get(&map, "tracestate"), Some("acme=1"));
+        assert_eq!(Extractor::keys(&map), vec!["traceparent", "tracestate"]);
+    }
+}
@@ -0,0 +1,304 @@
+use crate::{
+    Extractor, IdGenerator, Injector, ParseError, Propagator, RandomIdGenerator, TraceContext,
+};
+use std::fmt;
+
+/// Two propagation formats on the same carrier disagreeing about the trace-id or parent-id.
+///
+/// Passed to the handler set with
+/// [`CompositePropagator::on_conflict`](struct.CompositePropagator.html#method.on_conflict).
+#[derive(Debug)]
+pub struct Conflict<'a> {
+    /// The name of the format the TraceContext was extracted from.
+    pub winner: &'static str,
+    /// The TraceContext that was extracted.
+    pub winner_context: &'a TraceContext,
+    /// The name of a lower priority format with conflicting headers.
+    pub other: &'static str,
+    /// The TraceContext the lower priority format would have produced.
+    pub other_context: &'a TraceContext,
+}
+
+/// Tries several propagation formats in priority order.
+///
+/// Useful during migrations, when requests may carry the headers of several formats at once.
+///
+/// ## Examples
+/// ```
+/// use trace_context::{B3Propagator, CompositePropagator, JaegerPropagator, TraceContextPropagator};
+///
+/// let composite = CompositePropagator::new()
+///     .with(TraceContextPropagator::new())
+///     .with(B3Propagator::default())
+///     .with(JaegerPropagator::new())
+///     .on_conflict(|conflict| {
+///         eprintln!("{} headers conflict with {}", conflict.other, conflict.winner)
+///     });
+///
+/// let mut headers = http::HeaderMap::new();
+/// headers.insert("uber-trace-id", "a3ce929d0e0e4736:f067aa0ba902b7:0:1".parse().unwrap());
+///
+/// let (format, context) = composite.extract(&headers).unwrap().unwrap();
+/// assert_eq!(format, "jaeger");
+///
+/// // Write the headers of every format.
+/// let mut output = http::HeaderMap::new();
+/// composite.inject(&context.child(), &mut output);
+///
+/// assert!(output.contains_key("traceparent"));
+/// assert!(output.contains_key("x-b3-traceid"));
+/// assert!(output.contains_key("uber-trace-id"));
+/// ```
+#[derive(Default)]
+pub struct CompositePropagator {
+    propagators: Vec<Box<dyn Propagator + Send + Sync>>,
+    on_conflict: Option<Box<ConflictHandler>>,
+}
+
+type ConflictHandler = dyn Fn(&Conflict) + Send + Sync;
+
+impl CompositePropagator {
+    /// Create a CompositePropagator without any formats.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a format with a lower priority than the ones added before.
+    pub fn with<P: Propagator + Send + Sync + 'static>(mut self, propagator: P) -> Self {
+        self.propagators.push(Box::new(propagator));
+        self
+    }
+
+    /// Set a handler called when lower priority formats disagree with the winning one.
+    ///
+    /// Without a handler, extraction stops at the first format found. With one, the remaining
+    /// formats are extracted as well to compare them.
+    pub fn on_conflict<F: Fn(&Conflict) + Send + Sync + 'static>(mut self, handler: F) -> Self {
+        self.on_conflict = Some(Box::new(handler));
+        self
+    }
+
+    /// Create and return a TraceContext from the highest priority format present on the carrier,
+    /// along with the name of that format.
+    ///
+    /// Formats with invalid headers are skipped. If no format could be extracted and at least
+    /// one was invalid, the error of the first one is returned. Returns `Ok(None)` if the
+    /// carrier has no headers of any format.
+    pub fn extract<E: Extractor + ?Sized>(
+        &self,
+        carrier: &E,
+    ) -> Result<Option<(&'static str, TraceContext)>, ParseError> {
+        self.extract_with(carrier, &RandomIdGenerator::new())
+    }
+
+    /// Create and return a TraceContext like [`extract`](#method.extract), using `gen` to
+    /// generate its id.
+    pub fn extract_with<E: Extractor + ?Sized, G: IdGenerator + ?Sized>(
+        &self,
+        carrier: &E,
+        gen: &G,
+    ) -> Result<Option<(&'static str, TraceContext)>, ParseError> {
+        let carrier = &Carrier(carrier);
+        // Unlike `G`, `&G` is sized, so it can be passed as `&dyn IdGenerator`.
+        let gen = &gen;
+        let mut error = None;
+        let mut formats = self.propagators.iter();
+
+        let (winner, winner_context) = loop {
+            let propagator = match formats.next() {
+                Some(propagator) => propagator,
+                None => return error.map_or(Ok(None), Err),
+            };
+            match propagator.extract_with(carrier, gen) {
+                Ok(Some(context)) => break (propagator.name(), context),
+                Ok(None) => {}
+                Err(err) => {
+                    error.get_or_insert(err);
+                }
+            }
+        };
+
+        if let Some(on_conflict) = &self.on_conflict {
+            for propagator in formats {
+                if let Ok(Some(other_context)) = propagator.extract_with(carrier, gen) {
+                    if other_context.trace_id() != winner_context.trace_id()
+                        || other_context.parent_id() != winner_context.parent_id()
+                    {
+                        on_conflict(&Conflict {
+                            winner,
+                            winner_context: &winner_context,
+                            other: propagator.name(),
+                            other_context: &other_context,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(Some((winner, winner_context)))
+    }
+
+    /// Add the headers of every format for the TraceContext to the carrier.
+    pub fn inject<I: Injector + ?Sized>(&self, context: &TraceContext, carrier: &mut I) {
+        let carrier = &mut CarrierMut(carrier);
+        for propagator in &self.propagators {
+            propagator.inject(context, carrier);
+        }
+    }
+}
+
+impl Propagator for CompositePropagator {
+    fn name(&self) -> &'static str {
+        "composite"
+    }
+
+    fn extract_with(
+        &self,
+        carrier: &dyn Extractor,
+        gen: &dyn IdGenerator,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        Ok(CompositePropagator::extract_with(self, carrier, gen)?.map(|(_, context)| context))
+    }
+
+    fn inject(&self, context: &TraceContext, carrier: &mut dyn Injector) {
+        CompositePropagator::inject(self, context, carrier)
+    }
+}
+
+impl fmt::Debug for CompositePropagator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<_> = self.propagators.iter().map(|p| p.name()).collect();
+        f.debug_struct("CompositePropagator")
+            .field("propagators", &names)
+            .field("on_conflict", &self.on_conflict.is_some())
+            .finish()
+    }
+}
+
+/// Adapts an unsized Extractor into one that can be passed as `&dyn Extractor`.
+struct Carrier<'a, E: ?Sized>(&'a E);
+
+impl<'a, E: Extractor + ?Sized> Extractor for Carrier<'a, E> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys()
+    }
+}
+
+/// Adapts an unsized Injector into one that can be passed as `&mut dyn Injector`.
+struct CarrierMut<'a, I: ?Sized>(&'a mut I);
+
+impl<'a, I: Injector + ?Sized> Injector for CarrierMut<'a, I> {
+    fn set(&mut self, key: &str, value: String) {
+        self.0.set(key, value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CompositePropagator;
+    use crate::{
+        B3Propagator, JaegerPropagator, ParseError, TraceContext, TraceContextPropagator,
+        XRayPropagator,
+    };
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    const TRACEPARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01";
+
+    fn composite() -> CompositePropagator {
+        CompositePropagator::new()
+            .with(TraceContextPropagator::new())
+            .with(B3Propagator::default())
+            .with(JaegerPropagator::new())
+    }
+
+    #[test]
+    fn priority() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut carrier = HashMap::new();
+        carrier.insert("uber-trace-id".to_owned(), "abc:1f:0:1".to_owned());
+        let (format, context) = composite().extract(&carrier)?.unwrap();
+        assert_eq!(format, "jaeger");
+        assert_eq!(context.parent_id(), Some(0x1f.into()));
+
+        carrier.insert("traceparent".to_owned(), TRACEPARENT.to_owned());
+        let (format, context) = composite().extract(&carrier)?.unwrap();
+        assert_eq!(format, "tracecontext");
+        assert_eq!(context.parent_id(), Some("00f067aa0ba902b7".parse()?));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut carrier = HashMap::new();
+        carrier.insert("traceparent".to_owned(), "00-0af7".to_owned());
+        assert_eq!(
+            composite().extract(&carrier).unwrap_err(),
+            ParseError::BadLength("trace-id")
+        );
+
+        carrier.insert("uber-trace-id".to_owned(), "abc:1f:0:1".to_owned());
+        let (format, _) = composite().extract(&carrier)?.unwrap();
+        assert_eq!(format, "jaeger");
+        Ok(())
+    }
+
+    #[test]
+    fn missing() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let carrier: HashMap<String, String> = HashMap::new();
+        assert!(composite().extract(&carrier)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn conflicts() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let conflicts = Arc::new(Mutex::new(Vec::new()));
+        let logged = conflicts.clone();
+        let composite = composite().on_conflict(move |conflict| {
+            logged
+                .lock()
+                .unwrap()
+                .push((conflict.winner, conflict.other));
+        });
+
+        let context = TraceContext::from_bytes(TRACEPARENT.as_bytes())?;
+        let mut carrier = HashMap::new();
+        B3Propagator::default().inject(&context, &mut carrier);
+        carrier.insert("traceparent".to_owned(), TRACEPARENT.to_owned());
+        carrier.insert("uber-trace-id".to_owned(), "abc:1f:0:1".to_owned());
+
+        let (format, _) = composite.extract(&carrier)?.unwrap();
+        assert_eq!(format, "tracecontext");
+        assert_eq!(
+            *conflicts.lock().unwrap(),
+            vec![("tracecontext", "b3"), ("tracecontext", "jaeger")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn inject() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let context = TraceContext::new_root();
+        let mut carrier = HashMap::new();
+        composite()
+            .with(XRayPropagator::new())
+            .inject(&context, &mut carrier);
+        assert_eq!(carrier["traceparent"], context.to_string());
+        assert_eq!(carrier["x-b3-spanid"], context.id().to_string());
+        assert!(carrier.contains_key("uber-trace-id"));
+        assert!(carrier.contains_key("x-amzn-trace-id"));
+
+        let conflicts = Arc::new(Mutex::new(0));
+        let counted = conflicts.clone();
+        let composite = composite()
+            .with(XRayPropagator::new())
+            .on_conflict(move |_| *counted.lock().unwrap() += 1);
+        let (_, extracted) = composite.extract(&carrier)?.unwrap();
+        assert_eq!(extracted.parent_id(), Some(context.id()));
+        assert_eq!(*conflicts.lock().unwrap(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+use crate::{Sampler, SamplingDecision, TraceContext, TraceFlags, TraceId};
+use rand::Rng;
+
+/// The tracestate key of OpenTelemetry.
+const KEY: &str = "ot";
+
+/// The number of bits of randomness and thresholds.
+const BITS: u32 = 56;
+const MAX: u64 = 1 << BITS;
+const HEX_DIGITS: usize = 14;
+
+/// Samples a fraction of traces consistently across services, following the OpenTelemetry
+/// [probability sampling](https://opentelemetry.io/docs/specs/otel/trace/tracestate-probability-sampling/)
+/// spec.
+///
+/// Every trace has 56 bits of randomness, taken from the `rv` sub-key of the `ot` tracestate
+/// entry if present, or else from the trace id. A trace is sampled if its randomness is at
+/// least the rejection threshold of the sampler, which [`sample`](#method.sample) records in
+/// the `th` sub-key so downstream services can compute the
+/// [adjusted count](struct.TraceContext.html#method.adjusted_count).
+///
+/// ## Examples
+/// ```
+/// use trace_context::{ConsistentProbability, TraceContext, TraceFlags};
+///
+/// let sampler = ConsistentProbability::new(0.25);
+///
+/// // The trace ids generated by this crate are random.
+/// let mut context = TraceContext::new_root();
+/// context.set_flags(context.flags() | TraceFlags::RANDOM);
+/// if sampler.sample(&mut context).is_sampled() {
+///     assert_eq!(context.trace_state().get("ot"), Some("th:c"));
+///     assert_eq!(context.adjusted_count(), Some(4.0));
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsistentProbability {
+    threshold: Option<u64>,
+}
+
+impl ConsistentProbability {
+    /// Create a ConsistentProbability sampler sampling the given fraction of traces.
+    ///
+    /// The probability is rounded to the nearest one representable with 56 bits. A probability
+    /// of zero or less, or `NaN`, samples nothing.
+    pub fn new(probability: f64) -> Self {
+        if probability.is_nan() || probability <= 0.0 {
+            return Self { threshold: None };
+        }
+        let rejected = (1.0 - probability.min(1.0)) * MAX as f64;
+        Self {
+            threshold: Some((rejected.round() as u64).min(MAX - 1)),
+        }
+    }
+
+    /// Return the rejection threshold, or `None` if nothing is sampled.
+    pub fn threshold(&self) -> Option<u64> {
+        self.threshold
+    }
+
+    /// Decide whether the TraceContext is sampled, and update its sampled flag and the `ot`
+    /// tracestate entry accordingly.
+    ///
+    /// Call this on new roots, or on extracted TraceContexts to override the decision of the
+    /// caller. If the trace id of a root isn't known to be random, because the random trace
+    /// flag isn't set, explicit randomness is generated and stored in the `rv` sub-key.
+    pub fn sample(&self, context: &mut TraceContext) -> SamplingDecision {
+        let mut state = OtelState::parse(context);
+        let randomness = match state.randomness {
+            Some(randomness) => randomness,
+            None if context.parent_id().is_some()
+                || context.flags().contains(TraceFlags::RANDOM) =>
+            {
+                trace_id_randomness(context.trace_id())
+            }
+            None => {
+                let randomness = rand::thread_rng().gen::<u64>() & (MAX - 1);
+                state.randomness = Some(randomness);
+                randomness
+            }
+        };
+
+        let decision = decide(self.threshold, randomness);
+        state.threshold = match decision {
+            SamplingDecision::RecordAndSample => self.threshold,
+            SamplingDecision::Drop => None,
+        };
+        context.set_sampled(decision.is_sampled());
+        state.write(context);
+        decision
+    }
+}
+
+impl Sampler for ConsistentProbability {
+    /// Decide based on the randomness of the parent, or of the trace id for roots.
+    ///
+    /// Unlike [`sample`](#method.sample), this can't record the threshold in tracestate.
+    fn should_sample(&self, trace_id: TraceId, parent: Option<&TraceContext>) -> SamplingDecision {
+        let randomness = parent
+            .and_then(|parent| OtelState::parse(parent).randomness)
+            .unwrap_or_else(|| trace_id_randomness(trace_id));
+        decide(self.threshold, randomness)
+    }
+}
+
+fn decide(threshold: Option<u64>, randomness: u64) -> SamplingDecision {
+    match threshold {
+        Some(threshold) if randomness >= threshold => SamplingDecision::RecordAndSample,
+        _ => SamplingDecision::Drop,
+    }
+}
+
+/// The randomness of a trace id is its least significant 56 bits.
+fn trace_id_randomness(trace_id: TraceId) -> u64 {
+    u128::from(trace_id) as u64 & (MAX - 1)
+}
+
+/// Return the number of traces a sampled one represents: zero for unsampled traces, `None` if
+/// the threshold is unknown.
+pub(crate) fn adjusted_count(context: &TraceContext) -> Option<f64> {
+    if !context.sampled() {
+        return Some(0.0);
+    }
+    let threshold = OtelState::parse(context).threshold?;
+    Some(MAX as f64 / (MAX - threshold) as f64)
+}
+
+/// The sub-keys of the `ot` tracestate entry.
+struct OtelState {
+    threshold: Option<u64>,
+    randomness: Option<u64>,
+    other: Vec<String>,
+}
+
+impl OtelState {
+    fn parse(context: &TraceContext) -> Self {
+        let mut state = OtelState {
+            threshold: None,
+            randomness: None,
+            other: Vec::new(),
+        };
+        let value = match context.trace_state().get(KEY) {
+            Some(value) => value,
+            None => return state,
+        };
+
+        for field in value.split(';').filter(|field| !field.is_empty()) {
+            if let Some(th) = field.strip_prefix("th:") {
+                state.threshold = parse_threshold(th);
+            } else if let Some(rv) = field.strip_prefix("rv:") {
+                state.randomness = parse_randomness(rv);
+            } else {
+                state.other.push(field.to_owned());
+            }
+        }
+        state
+    }
+
+    fn write(&self, context: &mut TraceContext) {
+        let mut fields = Vec::new();
+        if let Some(threshold) = self.threshold {
+            fields.push(format!("th:{}", format_threshold(threshold)));
+        }
+        if let Some(randomness) = self.randomness {
+            fields.push(format!("rv:{:014x}", randomness));
+        }
+        fields.extend(self.other.iter().cloned());
+
+        let trace_state = context.trace_state_mut();
+        if fields.is_empty() {
+            trace_state.remove(KEY);
+        } else {
+            let _ = trace_state.insert(KEY, &fields.join(";"));
+        }
+    }
+}
+
+/// Parse a threshold of 1 to 14 hex digits, with trailing zeros omitted.
+fn parse_threshold(th: &str) -> Option<u64> {
+    if th.is_empty() || th.len() > HEX_DIGITS || !is_lower_hex(th) {
+        return None;
+    }
+    let value = u64::from_str_radix(th, 16).ok()?;
+    Some(value << (4 * (HEX_DIGITS - th.len())))
+}
+
+fn format_threshold(threshold: u64) -> String {
+    let th = format!("{:014x}", threshold);
+    match th.trim_end_matches('0') {
+        "" => "0".to_owned(),
+        th => th.to_owned(),
+    }
+}
+
+/// Parse explicit randomness of exactly 14 hex digits.
+fn parse_randomness(rv: &str) -> Option<u64> {
+    if rv.len() != HEX_DIGITS || !is_lower_hex(rv) {
+        return None;
+    }
+    u64::from_str_radix(rv, 16).ok()
+}
+
+fn is_lower_hex(value: &str) -> bool {
+    value
+        .bytes()
+        .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_threshold, parse_threshold, ConsistentProbability};
+    use crate::{Sampler, TraceContext, TraceFlags};
+
+    fn context(flags: &str, trace_state: Option<&str>) -> TraceContext {
+        let mut headers = http::HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            format!(
+                "00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-{}",
+                flags
+            )
+            .parse()
+            .unwrap(),
+        );
+        if let Some(trace_state) = trace_state {
+            headers.insert("tracestate", trace_state.parse().unwrap());
+        }
+        TraceContext::extract(&headers).unwrap()
+    }
+
+    #[test]
+    fn thresholds() {
+        assert_eq!(ConsistentProbability::new(1.0).threshold(), Some(0));
+        assert_eq!(
+            ConsistentProbability::new(0.5).threshold(),
+            Some(0x80000000000000)
+        );
+        assert_eq!(
+            ConsistentProbability::new(0.25).threshold(),
+            Some(0xc0000000000000)
+        );
+        assert_eq!(ConsistentProbability::new(0.0).threshold(), None);
+        assert_eq!(ConsistentProbability::new(f64::NAN).threshold(), None);
+
+        assert_eq!(format_threshold(0), "0");
+        assert_eq!(format_threshold(0xc0000000000000), "c");
+        assert_eq!(format_threshold(0x12345678900000), "123456789");
+        assert_eq!(parse_threshold("c"), Some(0xc0000000000000));
+        assert_eq!(parse_threshold("0"), Some(0));
+        assert_eq!(parse_threshold("C"), None);
+        assert_eq!(parse_threshold("123456789abcdef"), None);
+    }
+
+    #[test]
+    fn randomness_from_trace_id() {
+        // The least significant 56 bits of the trace id are 48eb211c80319c.
+        let mut sampled = context("02", None);
+        assert!(ConsistentProbability::new(0.75)
+            .sample(&mut sampled)
+            .is_sampled());
+        assert!(sampled.sampled());
+        assert_eq!(sampled.trace_state().get("ot"), Some("th:4"));
+
+        let mut dropped = context("03", Some("ot=th:0;xx:1,other=1"));
+        assert!(!ConsistentProbability::new(0.5)
+            .sample(&mut dropped)
+            .is_sampled());
+        assert!(!dropped.sampled());
+        assert_eq!(dropped.trace_state().get("ot"), Some("xx:1"));
+        assert_eq!(dropped.trace_state().get("other"), Some("1"));
+    }
+
+    #[test]
+    fn explicit_randomness() {
+        let mut context = context("00", Some("ot=rv:ffffffffffffff"));
+        assert!(ConsistentProbability::new(0.001)
+            .sample(&mut context)
+            .is_sampled());
+        assert_eq!(
+            context.trace_state().get("ot"),
+            Some("th:ffbe76c8b43958;rv:ffffffffffffff")
+        );
+
+        let sampler = ConsistentProbability::new(0.5);
+        assert!(sampler
+            .should_sample(context.trace_id(), Some(&context))
+            .is_sampled());
+        assert!(!sampler.should_sample(context.trace_id(), None).is_sampled());
+    }
+
+    #[test]
+    fn root_without_random_flag() {
+        let mut root = TraceContext::new_root();
+        assert!(!root.flags().contains(TraceFlags::RANDOM));
+        ConsistentProbability::new(0.5).sample(&mut root);
+        let ot = root.trace_state().get("ot").unwrap();
+        assert!(ot.contains("rv:"), "{}", ot);
+
+        let mut root = TraceContext::new_root();
+        root.set_flags(root.flags() | TraceFlags::RANDOM);
+        ConsistentProbability::new(1.0).sample(&mut root);
+        assert_eq!(root.trace_state().get("ot"), Some("th:0"));
+    }
+
+    #[test]
+    fn adjusted_count() {
+        assert_eq!(context("01", Some("ot=th:c")).adjusted_count(), Some(4.0));
+        assert_eq!(context("01", Some("ot=th:0")).adjusted_count(), Some(1.0));
+        assert_eq!(context("00", Some("ot=th:c")).adjusted_count(), Some(0.0));
+        assert_eq!(context("01", None).adjusted_count(), None);
+        assert_eq!(context("01", Some("ot=th:zz")).adjusted_count(), None);
+    }
+}
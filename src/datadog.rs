@@ -0,0 +1,411 @@
+use crate::{
+    decimal_field, hex_field, Deferral, Extractor, IdGenerator, Injector, ParseError, Propagator,
+    RandomIdGenerator, SpanId, TraceContext, TraceId,
+};
+
+const TRACE_ID: &str = "x-datadog-trace-id";
+const PARENT_ID: &str = "x-datadog-parent-id";
+const SAMPLING_PRIORITY: &str = "x-datadog-sampling-priority";
+const ORIGIN: &str = "x-datadog-origin";
+const TAGS: &str = "x-datadog-tags";
+
+const TAG_PREFIX: &str = "_dd.p.";
+const TRACE_ID_HIGH: &str = "_dd.p.tid";
+
+/// Converts between [Datadog](https://docs.datadoghq.com/tracing/trace_collection/trace_context_propagation/)
+/// `x-datadog-*` headers and TraceContext.
+///
+/// Datadog sends the lower 64 bits of the trace id as a decimal number in `x-datadog-trace-id`,
+/// and the upper 64 bits as 16 hex characters in the `_dd.p.tid` tag of `x-datadog-tags`.
+///
+/// ## Examples
+/// ```
+/// use trace_context::DatadogPropagator;
+///
+/// let mut headers = http::HeaderMap::new();
+/// headers.insert("x-datadog-trace-id", "5208512171318403364".parse().unwrap());
+/// headers.insert("x-datadog-parent-id", "5208512171318403364".parse().unwrap());
+/// headers.insert("x-datadog-sampling-priority", "1".parse().unwrap());
+/// headers.insert("x-datadog-tags", "_dd.p.tid=640cfd8d00000000".parse().unwrap());
+///
+/// let datadog = DatadogPropagator::new().w3c(true);
+/// let context = datadog.extract(&headers).unwrap().unwrap();
+///
+/// assert_eq!(context.trace_id().to_string(), "640cfd8d0000000048485a3953bb6124");
+/// assert_eq!(context.parent_id().unwrap().to_string(), "48485a3953bb6124");
+/// assert!(context.sampled());
+///
+/// // Emit Datadog and W3C headers side by side.
+/// let mut output = http::HeaderMap::new();
+/// datadog.inject(&context.child(), &mut output);
+///
+/// assert_eq!(output.get("x-datadog-trace-id").unwrap(), "5208512171318403364");
+/// assert!(output.contains_key("traceparent"));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DatadogPropagator {
+    w3c: bool,
+}
+
+impl DatadogPropagator {
+    /// Create a DatadogPropagator writing only the Datadog headers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether `inject` also writes the W3C `traceparent` and `tracestate` headers.
+    pub fn w3c(mut self, w3c: bool) -> Self {
+        self.w3c = w3c;
+        self
+    }
+
+    /// Create and return a TraceContext based on the `x-datadog-*` headers of the carrier.
+    ///
+    /// A sampling priority above zero marks the TraceContext sampled. Without a sampling priority
+    /// the decision is deferred to the receiver, see
+    /// [`TraceContext::sampling_deferred`](struct.TraceContext.html#method.sampling_deferred).
+    /// Headers without a parent id, as sent by Synthetics, result in a root TraceContext using
+    /// the given trace id. The origin and the propagated `_dd.p.*` tags are kept, see
+    /// [`VendorFields::datadog_origin`](struct.VendorFields.html#method.datadog_origin) and
+    /// [`VendorFields::datadog_tags`](struct.VendorFields.html#method.datadog_tags). Returns
+    /// `Ok(None)` if the carrier has no `x-datadog-trace-id` header.
+    pub fn extract<E: Extractor + ?Sized>(
+        &self,
+        carrier: &E,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        self.extract_with(carrier, &RandomIdGenerator::new())
+    }
+
+    /// Create and return a TraceContext like [`extract`](#method.extract), using `gen` to
+    /// generate its id.
+    pub fn extract_with<E: Extractor + ?Sized, G: IdGenerator + ?Sized>(
+        &self,
+        carrier: &E,
+        gen: &G,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        let low = match carrier.get(TRACE_ID) {
+            Some(trace_id) => decimal_field(trace_id, TRACE_ID)?,
+            None => return Ok(None),
+        };
+
+        let mut high = 0;
+        let mut tags = Vec::new();
+        for tag in carrier.get(TAGS).unwrap_or_default().split(',') {
+            let eq = match tag.find('=') {
+                Some(eq) => eq,
+                None => continue,
+            };
+            let (key, value) = (tag[..eq].trim(), tag[eq + 1..].trim());
+            if key == TRACE_ID_HIGH {
+                high = hex_field(Some(value.as_bytes()), TRACE_ID_HIGH, 16)?;
+            } else if key.starts_with(TAG_PREFIX) {
+                tags.push((key.to_owned(), value.to_owned()));
+            }
+        }
+
+        let trace_id = TraceId::from(high << 64 | u128::from(low));
+        if low == 0 {
+            return Err(ParseError::ZeroTraceId);
+        }
+
+        let mut context = match carrier.get(PARENT_ID) {
+            Some(parent_id) => {
+                let parent_id = SpanId::from(decimal_field(parent_id, PARENT_ID)?);
+                if !parent_id.is_valid() {
+                    return Err(ParseError::ZeroParentId);
+                }
+                TraceContext::remote(gen, trace_id, parent_id, Default::default())
+            }
+            None => TraceContext::remote_root(gen, trace_id),
+        };
+        match carrier.get(SAMPLING_PRIORITY) {
+            Some(priority) => {
+                let priority = priority
+                    .parse::<i8>()
+                    .map_err(|_| ParseError::InvalidSamplingState)?;
+                context.set_sampled(priority > 0);
+                context.vendor.datadog_priority = Some(priority);
+            }
+            None => context.defer_sampling(Deferral::Absent),
+        }
+        context.vendor.datadog_origin = carrier.get(ORIGIN).map(str::to_owned);
+        context.vendor.datadog_tags = tags;
+
+        Ok(Some(context))
+    }
+
+    /// Add the `x-datadog-*` headers for the TraceContext to the carrier, and the W3C headers
+    /// if enabled with [`w3c`](#method.w3c).
+    ///
+    /// The inbound sampling priority is written back if it agrees with the sampled flag, so
+    /// manual keep and reject decisions survive. Otherwise it's written as `1` or `0`,
+    /// depending on the sampled flag. A deferred sampling decision is written back by omitting
+    /// the priority.
+    pub fn inject<I: Injector + ?Sized>(&self, context: &TraceContext, carrier: &mut I) {
+        let trace_id = u128::from(context.trace_id());
+        carrier.set(TRACE_ID, (trace_id as u64).to_string());
+        carrier.set(PARENT_ID, u64::from(context.id()).to_string());
+        if !context.sampling_deferred() {
+            let priority = match context.vendor_fields().datadog_sampling_priority() {
+                Some(priority) if (priority > 0) == context.sampled() => priority,
+                _ => context.sampled() as i8,
+            };
+            carrier.set(SAMPLING_PRIORITY, priority.to_string());
+        }
+        if let Some(origin) = context.vendor_fields().datadog_origin() {
+            carrier.set(ORIGIN, origin.to_owned());
+        }
+
+        let mut tags = Vec::new();
+        if trace_id >> 64 != 0 {
+            tags.push(format!("{}={:016x}", TRACE_ID_HIGH, trace_id >> 64));
+        }
+        for (key, value) in context.vendor_fields().datadog_tags() {
+            tags.push(format!("{}={}", key, value));
+        }
+        if !tags.is_empty() {
+            carrier.set(TAGS, tags.join(","));
+        }
+
+        if self.w3c {
+            context.inject_into(carrier);
+        }
+    }
+}
+
+impl Propagator for DatadogPropagator {
+    fn name(&self) -> &'static str {
+        "datadog"
+    }
+
+    fn extract_with(
+        &self,
+        carrier: &dyn Extractor,
+        gen: &dyn IdGenerator,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        DatadogPropagator::extract_with(self, carrier, gen)
+    }
+
+    fn inject(&self, context: &TraceContext, carrier: &mut dyn Injector) {
+        DatadogPropagator::inject(self, context, carrier)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DatadogPropagator;
+    use crate::carrier::test::carrier;
+    use crate::{ParseError, TraceContext, TraceId};
+    use std::collections::HashMap;
+
+    #[test]
+    fn extract() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let context = DatadogPropagator::new()
+            .extract(&carrier(&[
+                ("x-datadog-trace-id", "1234"),
+                ("x-datadog-parent-id", "5678"),
+                ("x-datadog-sampling-priority", "-1"),
+                ("x-datadog-origin", "rum"),
+                (
+                    "x-datadog-tags",
+                    "_dd.p.dm=-4,_dd.p.tid=640cfd8d00000000,other=1",
+                ),
+            ]))?
+            .unwrap();
+        assert_eq!(
+            context.trace_id(),
+            TraceId::from(0x640c_fd8d_0000_0000 << 64 | 1234)
+        );
+        assert_eq!(context.parent_id(), Some(5678.into()));
+        assert!(!context.sampled());
+        assert_eq!(context.vendor_fields().datadog_origin(), Some("rum"));
+        assert_eq!(
+            context.vendor_fields().datadog_tags(),
+            &[("_dd.p.dm".to_string(), "-4".to_string())][..]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn without_parent() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let context = DatadogPropagator::new()
+            .extract(&carrier(&[
+                ("x-datadog-trace-id", "1234"),
+                ("x-datadog-origin", "synthetics"),
+            ]))?
+            .unwrap();
+        assert_eq!(context.trace_id(), TraceId::from(1234));
+        assert_eq!(context.parent_id(), None);
+        assert!(!context.sampled());
+        assert!(context.sampling_deferred());
+        Ok(())
+    }
+
+    #[test]
+    fn defer() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let headers = [
+            &[
+                ("x-datadog-trace-id", "1234"),
+                ("x-datadog-parent-id", "5678"),
+            ][..],
+            &[("x-datadog-trace-id", "1234")][..],
+        ];
+        for headers in &headers {
+            let context = DatadogPropagator::new()
+                .extract(&carrier(headers))?
+                .unwrap();
+            assert!(!context.sampled());
+            assert!(context.sampling_deferred());
+
+            let mut injected = HashMap::new();
+            DatadogPropagator::new().inject(&context.child(), &mut injected);
+            assert!(!injected.contains_key("x-datadog-sampling-priority"));
+            let extracted = DatadogPropagator::new().extract(&injected)?.unwrap();
+            assert!(extracted.sampling_deferred());
+        }
+
+        let mut context = DatadogPropagator::new()
+            .extract(&carrier(&[("x-datadog-trace-id", "1234")]))?
+            .unwrap();
+        context.set_sampled(true);
+        let mut injected = HashMap::new();
+        DatadogPropagator::new().inject(&context, &mut injected);
+        assert_eq!(injected["x-datadog-sampling-priority"], "1");
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        let datadog = DatadogPropagator::new();
+        assert_eq!(
+            datadog
+                .extract(&carrier(&[("x-datadog-trace-id", "0x12")]))
+                .unwrap_err(),
+            ParseError::NotDecimal("x-datadog-trace-id")
+        );
+        assert_eq!(
+            datadog
+                .extract(&carrier(&[("x-datadog-trace-id", "18446744073709551616")]))
+                .unwrap_err(),
+            ParseError::NotDecimal("x-datadog-trace-id")
+        );
+        assert_eq!(
+            datadog
+                .extract(&carrier(&[("x-datadog-trace-id", "0")]))
+                .unwrap_err(),
+            ParseError::ZeroTraceId
+        );
+        assert_eq!(
+            datadog
+                .extract(&carrier(&[
+                    ("x-datadog-trace-id", "1"),
+                    ("x-datadog-parent-id", "0")
+                ]))
+                .unwrap_err(),
+            ParseError::ZeroParentId
+        );
+        assert_eq!(
+            datadog
+                .extract(&carrier(&[
+                    ("x-datadog-trace-id", "1"),
+                    ("x-datadog-sampling-priority", "keep")
+                ]))
+                .unwrap_err(),
+            ParseError::InvalidSamplingState
+        );
+        assert_eq!(
+            datadog
+                .extract(&carrier(&[
+                    ("x-datadog-trace-id", "1"),
+                    ("x-datadog-tags", "_dd.p.tid=640CFD8D00000000")
+                ]))
+                .unwrap_err(),
+            ParseError::NotLowercaseHex("_dd.p.tid")
+        );
+    }
+
+    #[test]
+    fn inject() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let parent = DatadogPropagator::new()
+            .extract(&carrier(&[
+                ("x-datadog-trace-id", "1234"),
+                ("x-datadog-parent-id", "5678"),
+                ("x-datadog-sampling-priority", "2"),
+                ("x-datadog-origin", "rum"),
+                ("x-datadog-tags", "_dd.p.tid=640cfd8d00000000,_dd.p.dm=-4"),
+            ]))?
+            .unwrap();
+        let child = parent.child();
+        let mut carrier = HashMap::new();
+        DatadogPropagator::new().inject(&child, &mut carrier);
+        assert_eq!(carrier["x-datadog-trace-id"], "1234");
+        assert_eq!(
+            carrier["x-datadog-parent-id"],
+            u64::from(child.id()).to_string()
+        );
+        assert_eq!(carrier["x-datadog-sampling-priority"], "2");
+        assert_eq!(carrier["x-datadog-origin"], "rum");
+        assert_eq!(
+            carrier["x-datadog-tags"],
+            "_dd.p.tid=640cfd8d00000000,_dd.p.dm=-4"
+        );
+        assert!(!carrier.contains_key("traceparent"));
+
+        let extracted = DatadogPropagator::new().extract(&carrier)?.unwrap();
+        assert_eq!(extracted.trace_id(), parent.trace_id());
+        assert_eq!(extracted.parent_id(), Some(child.id()));
+        assert_eq!(
+            extracted.vendor_fields().datadog_sampling_priority(),
+            Some(2)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn inject_priority() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let priority = |context: &TraceContext| {
+            let mut carrier = HashMap::new();
+            DatadogPropagator::new().inject(context, &mut carrier);
+            carrier["x-datadog-sampling-priority"].clone()
+        };
+
+        let mut context = DatadogPropagator::new()
+            .extract(&carrier(&[
+                ("x-datadog-trace-id", "1234"),
+                ("x-datadog-parent-id", "5678"),
+                ("x-datadog-sampling-priority", "-1"),
+            ]))?
+            .unwrap();
+        assert_eq!(
+            context.vendor_fields().datadog_sampling_priority(),
+            Some(-1)
+        );
+        assert_eq!(priority(&context), "-1");
+        assert_eq!(priority(&context.child()), "-1");
+
+        context.set_sampled(true);
+        assert_eq!(priority(&context), "1");
+        context.set_sampled(false);
+        assert_eq!(priority(&context), "-1");
+
+        assert_eq!(priority(&TraceContext::new_root()), "1");
+        Ok(())
+    }
+
+    #[test]
+    fn inject_w3c() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let context = TraceContext::new_root();
+        let mut carrier = HashMap::new();
+        DatadogPropagator::new()
+            .w3c(true)
+            .inject(&context, &mut carrier);
+        assert_eq!(carrier["traceparent"], context.to_string());
+        assert!(!carrier.contains_key("x-datadog-origin"));
+
+        let datadog = DatadogPropagator::new().extract(&carrier)?.unwrap();
+        let w3c = TraceContext::extract_from(&carrier)?;
+        assert_eq!(datadog.trace_id(), w3c.trace_id());
+        assert_eq!(datadog.parent_id(), w3c.parent_id());
+        Ok(())
+    }
+}
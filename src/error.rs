@@ -0,0 +1,80 @@
+use std::fmt;
+
+/// An error returned when trace context headers can't be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// The header contains characters outside of visible ASCII.
+    NonAscii,
+    /// A field of the header is missing.
+    MissingField(&'static str),
+    /// A field, or the header itself, doesn't have the length required by the spec.
+    BadLength(&'static str),
+    /// A field contains characters other than lowercase hex digits.
+    NotLowercaseHex(&'static str),
+    /// A field that must be a decimal number isn't one, or doesn't fit its type.
+    NotDecimal(&'static str),
+    /// The version is `ff`, which the spec forbids.
+    InvalidVersion,
+    /// The trace-id is all zeros.
+    ZeroTraceId,
+    /// The parent-id is all zeros.
+    ZeroParentId,
+    /// A `tracestate` list-member has a key that doesn't match the spec grammar.
+    InvalidTraceStateKey,
+    /// A `tracestate` list-member has a missing value or one that doesn't match the spec grammar.
+    InvalidTraceStateValue,
+    /// The same key appears more than once in `tracestate`.
+    DuplicateTraceStateKey,
+    /// `tracestate` has more list-members than the spec allows.
+    TooManyTraceStateMembers,
+    /// A `baggage` key or property key isn't a valid token.
+    InvalidBaggageKey,
+    /// A `baggage` list-member has a missing value or one with invalid characters.
+    InvalidBaggageValue,
+    /// `baggage` has more list-members than the spec allows.
+    TooManyBaggageMembers,
+    /// `baggage` is longer than the spec allows.
+    BaggageTooLarge,
+    /// A sampling decision isn't one of the values allowed by the propagation format.
+    InvalidSamplingState,
+    /// A header is longer than the configured limit.
+    HeaderTooLong(&'static str),
+    /// The request has more than one `traceparent` header.
+    DuplicateTraceparent,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::NonAscii => write!(f, "header contains non-ASCII characters"),
+            ParseError::MissingField(field) => write!(f, "missing field {}", field),
+            ParseError::BadLength(field) => write!(f, "{} has an invalid length", field),
+            ParseError::NotLowercaseHex(field) => write!(f, "{} is not lowercase hex", field),
+            ParseError::NotDecimal(field) => write!(f, "{} is not a decimal number", field),
+            ParseError::InvalidVersion => write!(f, "traceparent version ff is not allowed"),
+            ParseError::ZeroTraceId => write!(f, "trace-id must not be all zeros"),
+            ParseError::ZeroParentId => write!(f, "parent-id must not be all zeros"),
+            ParseError::InvalidTraceStateKey => write!(f, "tracestate contains an invalid key"),
+            ParseError::InvalidTraceStateValue => {
+                write!(f, "tracestate contains an invalid value")
+            }
+            ParseError::DuplicateTraceStateKey => {
+                write!(f, "tracestate contains a duplicate key")
+            }
+            ParseError::TooManyTraceStateMembers => {
+                write!(f, "tracestate contains too many list-members")
+            }
+            ParseError::InvalidBaggageKey => write!(f, "baggage contains an invalid key"),
+            ParseError::InvalidBaggageValue => write!(f, "baggage contains an invalid value"),
+            ParseError::TooManyBaggageMembers => {
+                write!(f, "baggage contains too many list-members")
+            }
+            ParseError::BaggageTooLarge => write!(f, "baggage exceeds 8192 bytes"),
+            ParseError::InvalidSamplingState => write!(f, "invalid sampling state"),
+            ParseError::HeaderTooLong(header) => write!(f, "{} exceeds the length limit", header),
+            ParseError::DuplicateTraceparent => write!(f, "multiple traceparent headers"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
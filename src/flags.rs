@@ -0,0 +1,162 @@
+use std::fmt;
+use std::ops::BitOr;
+
+/// The trace-flags field of a `traceparent` header.
+///
+/// ## Examples
+/// ```
+/// use trace_context::TraceFlags;
+///
+/// let mut flags = TraceFlags::SAMPLED;
+/// flags.insert(TraceFlags::RANDOM);
+///
+/// assert!(flags.contains(TraceFlags::SAMPLED | TraceFlags::RANDOM));
+/// assert_eq!(flags.to_string(), "03");
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TraceFlags(u8);
+
+impl TraceFlags {
+    /// The caller may have recorded trace data.
+    pub const SAMPLED: TraceFlags = TraceFlags(0b0000_0001);
+
+    /// The right-most 7 bytes of the trace-id are random, as defined by Trace Context Level 2.
+    pub const RANDOM: TraceFlags = TraceFlags(0b0000_0010);
+
+    /// All the flags understood by this crate.
+    const KNOWN: TraceFlags = TraceFlags(Self::SAMPLED.0 | Self::RANDOM.0);
+
+    /// Create TraceFlags from the raw trace-flags byte, including any unknown bits.
+    pub fn from_bits(bits: u8) -> Self {
+        TraceFlags(bits)
+    }
+
+    /// Return the raw trace-flags byte.
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Returns true if all the bits in `flags` are set.
+    pub fn contains(self, flags: TraceFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    /// Set all the bits in `flags`.
+    pub fn insert(&mut self, flags: TraceFlags) {
+        self.0 |= flags.0;
+    }
+
+    /// Clear all the bits in `flags`.
+    pub fn remove(&mut self, flags: TraceFlags) {
+        self.0 &= !flags.0;
+    }
+
+    /// Set or clear all the bits in `flags`.
+    pub fn set(&mut self, flags: TraceFlags, value: bool) {
+        if value {
+            self.insert(flags);
+        } else {
+            self.remove(flags);
+        }
+    }
+
+    /// Returns true if any bits not understood by this crate are set.
+    pub fn has_unknown(self) -> bool {
+        self.0 & !Self::KNOWN.0 != 0
+    }
+
+    /// Return the flags a child should inherit according to `policy`.
+    ///
+    /// ## Examples
+    /// ```
+    /// use trace_context::{TraceFlags, UnknownFlags};
+    ///
+    /// let flags = TraceFlags::from_bits(0b1000_0001);
+    ///
+    /// assert_eq!(flags.for_child(UnknownFlags::Clear), TraceFlags::SAMPLED);
+    /// assert_eq!(flags.for_child(UnknownFlags::Propagate), flags);
+    /// ```
+    pub fn for_child(self, policy: UnknownFlags) -> Self {
+        match policy {
+            UnknownFlags::Clear => TraceFlags(self.0 & Self::KNOWN.0),
+            UnknownFlags::Propagate => self,
+        }
+    }
+}
+
+impl BitOr for TraceFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        TraceFlags(self.0 | rhs.0)
+    }
+}
+
+impl fmt::Display for TraceFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02x}", self.0)
+    }
+}
+
+impl fmt::LowerHex for TraceFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Debug for TraceFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("TraceFlags")
+            .field(&format_args!("{:#010b}", self.0))
+            .finish()
+    }
+}
+
+/// How trace-flags bits not understood by this crate are passed on to children.
+///
+/// The spec requires that flags a vendor doesn't understand are cleared, which is what
+/// [`TraceContext::child`](struct.TraceContext.html#method.child) does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownFlags {
+    /// Clear unknown bits.
+    Clear,
+    /// Pass unknown bits on unchanged.
+    Propagate,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TraceFlags, UnknownFlags};
+
+    #[test]
+    fn set_and_clear() {
+        let mut flags = TraceFlags::default();
+        flags.set(TraceFlags::SAMPLED, true);
+        assert!(flags.contains(TraceFlags::SAMPLED));
+        assert!(!flags.contains(TraceFlags::RANDOM));
+        flags.insert(TraceFlags::RANDOM);
+        assert_eq!(flags.bits(), 0b11);
+        flags.remove(TraceFlags::SAMPLED);
+        assert_eq!(flags, TraceFlags::RANDOM);
+        flags.set(TraceFlags::RANDOM, false);
+        assert_eq!(flags.bits(), 0);
+    }
+
+    #[test]
+    fn unknown_bits() {
+        let flags = TraceFlags::from_bits(0b0000_0110);
+        assert!(flags.has_unknown());
+        assert_eq!(flags.for_child(UnknownFlags::Clear), TraceFlags::RANDOM);
+        assert!(!flags.for_child(UnknownFlags::Clear).has_unknown());
+        assert_eq!(flags.for_child(UnknownFlags::Propagate).bits(), 0b0000_0110);
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(TraceFlags::from_bits(0xa1).to_string(), "a1");
+        assert_eq!(
+            format!("{:?}", TraceFlags::SAMPLED),
+            "TraceFlags(0b00000001)"
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{
+    decimal_field, hex_field, Extractor, IdGenerator, Injector, ParseError, Propagator,
+    RandomIdGenerator, SpanId, TraceContext, TraceId,
+};
+
+const HEADER: &str = "x-cloud-trace-context";
+
+/// Converts between the [Google Cloud](https://cloud.google.com/trace/docs/trace-context#legacy-http-header)
+/// `X-Cloud-Trace-Context` header and TraceContext.
+///
+/// The header has the form `TRACE_ID/SPAN_ID;o=OPTIONS`, where the span id is a decimal number
+/// and `o=1` marks the trace sampled.
+///
+/// ## Examples
+/// ```
+/// use trace_context::GcpPropagator;
+///
+/// let mut headers = http::HeaderMap::new();
+/// headers.insert(
+///     "x-cloud-trace-context",
+///     "105445aa7843bc8bf206b12000100000/1;o=1".parse().unwrap(),
+/// );
+///
+/// let context = GcpPropagator::new().extract(&headers).unwrap().unwrap();
+///
+/// assert_eq!(context.trace_id().to_string(), "105445aa7843bc8bf206b12000100000");
+/// assert_eq!(context.parent_id().unwrap().to_string(), "0000000000000001");
+/// assert!(context.sampled());
+/// assert_eq!(
+///     GcpPropagator::trace_resource("my-project", context.trace_id()),
+///     "projects/my-project/traces/105445aa7843bc8bf206b12000100000",
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcpPropagator;
+
+impl GcpPropagator {
+    /// Create a GcpPropagator.
+    pub fn new() -> Self {
+        GcpPropagator
+    }
+
+    /// Create and return a TraceContext based on the `X-Cloud-Trace-Context` header of the
+    /// carrier.
+    ///
+    /// A header without the `o` option leaves the TraceContext unsampled. Returns `Ok(None)` if
+    /// the carrier has no `X-Cloud-Trace-Context` header.
+    pub fn extract<E: Extractor + ?Sized>(
+        &self,
+        carrier: &E,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        self.extract_with(carrier, &RandomIdGenerator::new())
+    }
+
+    /// Create and return a TraceContext like [`extract`](#method.extract), using `gen` to
+    /// generate its id.
+    pub fn extract_with<E: Extractor + ?Sized, G: IdGenerator + ?Sized>(
+        &self,
+        carrier: &E,
+        gen: &G,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        let header = match carrier.get(HEADER) {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+
+        let (ids, options) = match header.find(';') {
+            Some(semicolon) => (&header[..semicolon], Some(&header[semicolon + 1..])),
+            None => (header, None),
+        };
+        let mut parts = ids.split('/');
+        let trace_id = TraceId::from(hex_field(parts.next().map(str::as_bytes), "trace-id", 32)?);
+        let span_id = match parts.next() {
+            Some(span_id) => SpanId::from(decimal_field(span_id, "span-id")?),
+            None => return Err(ParseError::MissingField("span-id")),
+        };
+        if parts.next().is_some() {
+            return Err(ParseError::BadLength(HEADER));
+        }
+
+        if !trace_id.is_valid() {
+            return Err(ParseError::ZeroTraceId);
+        }
+        if !span_id.is_valid() {
+            return Err(ParseError::ZeroParentId);
+        }
+
+        let mut context = TraceContext::remote(gen, trace_id, span_id, Default::default());
+        match options {
+            Some("o=1") => context.set_sampled(true),
+            Some("o=0") | None => {}
+            Some(_) => return Err(ParseError::InvalidSamplingState),
+        }
+        Ok(Some(context))
+    }
+
+    /// Add the `X-Cloud-Trace-Context` header for the TraceContext to the carrier.
+    pub fn inject<I: Injector + ?Sized>(&self, context: &TraceContext, carrier: &mut I) {
+        carrier.set(
+            HEADER,
+            format!(
+                "{}/{};o={}",
+                context.trace_id(),
+                u64::from(context.id()),
+                if context.sampled() { 1 } else { 0 }
+            ),
+        );
+    }
+
+    /// Return the `projects/{project_id}/traces/{trace_id}` resource name of a trace, as
+    /// expected by the `logging.googleapis.com/trace` field of Cloud Logging entries.
+    pub fn trace_resource(project_id: &str, trace_id: TraceId) -> String {
+        format!("projects/{}/traces/{}", project_id, trace_id)
+    }
+}
+
+impl Propagator for GcpPropagator {
+    fn name(&self) -> &'static str {
+        "gcp"
+    }
+
+    fn extract_with(
+        &self,
+        carrier: &dyn Extractor,
+        gen: &dyn IdGenerator,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        GcpPropagator::extract_with(self, carrier, gen)
+    }
+
+    fn inject(&self, context: &TraceContext, carrier: &mut dyn Injector) {
+        GcpPropagator::inject(self, context, carrier)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GcpPropagator, HEADER};
+    use crate::carrier::test::carrier;
+    use crate::{ParseError, SpanId, TraceId};
+    use std::collections::HashMap;
+
+    #[test]
+    fn extract() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let context = GcpPropagator::new()
+            .extract(&carrier(&[(
+                HEADER,
+                "105445aa7843bc8bf206b12000100000/18446744073709551615;o=0",
+            )]))?
+            .unwrap();
+        assert_eq!(
+            context.trace_id(),
+            "105445aa7843bc8bf206b12000100000".parse()?
+        );
+        assert_eq!(context.parent_id(), Some(SpanId::from(u64::MAX)));
+        assert!(!context.sampled());
+
+        let context = GcpPropagator::new()
+            .extract(&carrier(&[(HEADER, "105445aa7843bc8bf206b12000100000/1")]))?
+            .unwrap();
+        assert!(!context.sampled());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        let gcp = GcpPropagator::new();
+        assert_eq!(
+            gcp.extract(&carrier(&[(HEADER, "105445aa7843bc8bf206b12000100000")]))
+                .unwrap_err(),
+            ParseError::MissingField("span-id")
+        );
+        assert_eq!(
+            gcp.extract(&carrier(&[(
+                HEADER,
+                "105445aa7843bc8bf206b12000100000/abc;o=1"
+            )]))
+            .unwrap_err(),
+            ParseError::NotDecimal("span-id")
+        );
+        assert_eq!(
+            gcp.extract(&carrier(&[(
+                HEADER,
+                "105445AA7843BC8BF206B12000100000/1;o=1"
+            )]))
+            .unwrap_err(),
+            ParseError::NotLowercaseHex("trace-id")
+        );
+        assert_eq!(
+            gcp.extract(&carrier(&[(
+                HEADER,
+                "00000000000000000000000000000000/1;o=1"
+            )]))
+            .unwrap_err(),
+            ParseError::ZeroTraceId
+        );
+        assert_eq!(
+            gcp.extract(&carrier(&[(
+                HEADER,
+                "105445aa7843bc8bf206b12000100000/0;o=1"
+            )]))
+            .unwrap_err(),
+            ParseError::ZeroParentId
+        );
+        assert_eq!(
+            gcp.extract(&carrier(&[(
+                HEADER,
+                "105445aa7843bc8bf206b12000100000/1;o=2"
+            )]))
+            .unwrap_err(),
+            ParseError::InvalidSamplingState
+        );
+    }
+
+    #[test]
+    fn inject() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let parent = GcpPropagator::new()
+            .extract(&carrier(&[(
+                HEADER,
+                "105445aa7843bc8bf206b12000100000/1;o=1",
+            )]))?
+            .unwrap();
+        let mut carrier = HashMap::new();
+        GcpPropagator::new().inject(&parent, &mut carrier);
+        assert_eq!(
+            carrier["x-cloud-trace-context"],
+            format!(
+                "105445aa7843bc8bf206b12000100000/{};o=1",
+                u64::from(parent.id())
+            )
+        );
+
+        let child = GcpPropagator::new().extract(&carrier)?.unwrap();
+        assert_eq!(child.trace_id(), parent.trace_id());
+        assert_eq!(child.parent_id(), Some(parent.id()));
+        assert!(child.sampled());
+        Ok(())
+    }
+
+    #[test]
+    fn trace_resource() {
+        assert_eq!(
+            GcpPropagator::trace_resource("p", TraceId::from(1)),
+            "projects/p/traces/00000000000000000000000000000001"
+        );
+    }
+}
@@ -0,0 +1,303 @@
+use crate::{SpanId, TraceId, TraceIdFormat};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::Mutex;
+
+/// Generates the trace and span ids of new TraceContext objects.
+///
+/// The spec considers all-zero ids invalid, so implementations should never return them. The
+/// generators provided by this crate guarantee this; a zero id from any other implementation is
+/// replaced by 1 when the TraceContext is created.
+///
+/// Pass an implementation to constructors such as
+/// [`TraceContext::new_root_with`](struct.TraceContext.html#method.new_root_with) to control how
+/// ids are generated.
+pub trait IdGenerator {
+    /// Generate a new trace id.
+    fn new_trace_id(&self) -> TraceId;
+
+    /// Generate a new span id.
+    fn new_span_id(&self) -> SpanId;
+}
+
+impl<G: IdGenerator + ?Sized> IdGenerator for &G {
+    fn new_trace_id(&self) -> TraceId {
+        (**self).new_trace_id()
+    }
+
+    fn new_span_id(&self) -> SpanId {
+        (**self).new_span_id()
+    }
+}
+
+/// Generate a trace id with `gen`, replacing an invalid zero id by 1.
+pub(crate) fn trace_id<G: IdGenerator + ?Sized>(gen: &G) -> TraceId {
+    TraceId::from(u128::from(gen.new_trace_id()).max(1))
+}
+
+/// Generate a span id with `gen`, replacing an invalid zero id by 1.
+pub(crate) fn span_id<G: IdGenerator + ?Sized>(gen: &G) -> SpanId {
+    SpanId::from(u64::from(gen.new_span_id()).max(1))
+}
+
+/// Generates random ids using the thread-local random number generator.
+///
+/// This is what `new_root`, `child`, `extract` and the other methods that don't take an
+/// IdGenerator use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RandomIdGenerator {
+    format: TraceIdFormat,
+}
+
+impl RandomIdGenerator {
+    /// Create a RandomIdGenerator generating fully random trace ids.
+    pub fn new() -> Self {
+        Self::with_format(TraceIdFormat::Random)
+    }
+
+    /// Create a RandomIdGenerator generating trace ids in the given format.
+    pub fn with_format(format: TraceIdFormat) -> Self {
+        Self { format }
+    }
+}
+
+impl Default for RandomIdGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IdGenerator for RandomIdGenerator {
+    fn new_trace_id(&self) -> TraceId {
+        self.format.generate(&mut rand::thread_rng())
+    }
+
+    fn new_span_id(&self) -> SpanId {
+        SpanId::generate(&mut rand::thread_rng())
+    }
+}
+
+/// Generates a reproducible sequence of ids from a seed, which is useful in tests.
+///
+/// ## Examples
+/// ```
+/// use trace_context::{IdGenerator, SeededIdGenerator, TraceContext};
+///
+/// let a = TraceContext::new_root_with(&SeededIdGenerator::new(42));
+/// let b = TraceContext::new_root_with(&SeededIdGenerator::new(42));
+///
+/// assert_eq!(a.trace_id(), b.trace_id());
+/// assert_eq!(a.id(), b.id());
+/// ```
+#[derive(Debug)]
+pub struct SeededIdGenerator {
+    rng: Mutex<StdRng>,
+}
+
+impl SeededIdGenerator {
+    /// Create a SeededIdGenerator from a seed.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+}
+
+impl IdGenerator for SeededIdGenerator {
+    fn new_trace_id(&self) -> TraceId {
+        TraceIdFormat::Random.generate(&mut *self.rng.lock().unwrap())
+    }
+
+    fn new_span_id(&self) -> SpanId {
+        SpanId::generate(&mut *self.rng.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IdGenerator, RandomIdGenerator, SeededIdGenerator};
+    use crate::{
+        B3Propagator, CompositePropagator, ParseError, SpanId, TraceContext,
+        TraceContextPropagator, TraceId, TraceIdFormat, XRayPropagator,
+    };
+    use proptest::prelude::*;
+    use rand::RngCore;
+
+    /// An RNG replaying a fixed sequence of values, returning zero once they run out.
+    struct MockRng(std::vec::IntoIter<u64>);
+
+    impl RngCore for MockRng {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0.next().unwrap_or(0)
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for chunk in dest.chunks_mut(8) {
+                let bytes = self.next_u64().to_le_bytes();
+                chunk.copy_from_slice(&bytes[..chunk.len()]);
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    /// A generator backed by an RNG that only ever returns zero.
+    struct ZeroIdGenerator;
+
+    impl IdGenerator for ZeroIdGenerator {
+        fn new_trace_id(&self) -> TraceId {
+            TraceIdFormat::Random.generate(&mut MockRng(Vec::new().into_iter()))
+        }
+
+        fn new_span_id(&self) -> SpanId {
+            SpanId::generate(&mut MockRng(Vec::new().into_iter()))
+        }
+    }
+
+    /// A generator ignoring the contract and returning zero ids.
+    struct ZeroIds;
+
+    impl IdGenerator for ZeroIds {
+        fn new_trace_id(&self) -> TraceId {
+            TraceId::from(0)
+        }
+
+        fn new_span_id(&self) -> SpanId {
+            SpanId::from(0)
+        }
+    }
+
+    #[test]
+    fn zero_ids() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let root = TraceContext::new_root_with(&ZeroIds);
+        assert_eq!(root.trace_id(), TraceId::from(1));
+        assert_eq!(root.id(), SpanId::from(1));
+        assert_eq!(root.child_with(&ZeroIds).id(), SpanId::from(1));
+
+        let traceparent = b"00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01";
+        let remote = TraceContext::from_bytes_with(traceparent, &ZeroIds)?;
+        assert_eq!(remote.id(), SpanId::from(1));
+
+        let mut carrier = std::collections::HashMap::new();
+        carrier.insert(
+            "x-amzn-trace-id".to_owned(),
+            "Root=1-5759e988-bd862e3fe1be46a994272793".to_owned(),
+        );
+        let remote_root = XRayPropagator::new()
+            .extract_with(&carrier, &ZeroIds)?
+            .unwrap();
+        assert_eq!(remote_root.id(), SpanId::from(1));
+        Ok(())
+    }
+
+    #[test]
+    fn zero_rng() {
+        let mut rng = MockRng(Vec::new().into_iter());
+        assert!(SpanId::generate(&mut rng).is_valid());
+        assert!(TraceIdFormat::Random.generate(&mut rng).is_valid());
+        assert!(TraceIdFormat::TimePrefixed.generate(&mut rng).is_valid());
+    }
+
+    #[test]
+    fn zero_rng_contexts() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let root = TraceContext::new_root_with(&ZeroIdGenerator);
+        assert!(root.trace_id().is_valid());
+        assert!(root.id().is_valid());
+
+        let child = root.child_with(&ZeroIdGenerator);
+        assert!(child.id().is_valid());
+
+        let extracted = TraceContext::extract_with(&http::HeaderMap::new(), &ZeroIdGenerator)?;
+        assert!(extracted.trace_id().is_valid());
+        assert!(extracted.id().is_valid());
+
+        // A strict parser must accept what we emit.
+        let mut headers = http::HeaderMap::new();
+        child.inject(&mut headers);
+        TraceContext::extract(&headers)?;
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn mocked_ids_are_valid(
+            values in prop::collection::vec(prop_oneof![Just(0u64), any::<u64>()], 0..6)
+        ) {
+            let mut rng = MockRng(values.into_iter());
+            prop_assert!(SpanId::generate(&mut rng).is_valid());
+            prop_assert!(TraceIdFormat::Random.generate(&mut rng).is_valid());
+            prop_assert!(TraceIdFormat::TimePrefixed.generate(&mut rng).is_valid());
+        }
+
+        #[test]
+        fn seeded_ids_are_valid(seed in any::<u64>()) {
+            let generator = SeededIdGenerator::new(seed);
+            let root = TraceContext::new_root_with(&generator);
+            prop_assert!(root.trace_id().is_valid());
+            prop_assert!(root.id().is_valid());
+            prop_assert!(root.child_with(&generator).id().is_valid());
+        }
+    }
+
+    #[test]
+    fn seeded_is_reproducible() {
+        let a = SeededIdGenerator::new(7);
+        let b = SeededIdGenerator::new(7);
+        for _ in 0..10 {
+            assert_eq!(a.new_trace_id(), b.new_trace_id());
+            assert_eq!(a.new_span_id(), b.new_span_id());
+        }
+        assert_ne!(
+            SeededIdGenerator::new(8).new_span_id(),
+            SeededIdGenerator::new(7).new_span_id()
+        );
+    }
+
+    #[test]
+    fn seeded_extraction() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut carrier = std::collections::HashMap::new();
+        carrier.insert(
+            "b3".to_owned(),
+            "a3ce929d0e0e4736-00f067aa0ba902b7-1".to_owned(),
+        );
+        let composite = CompositePropagator::new()
+            .with(TraceContextPropagator::new())
+            .with(B3Propagator::default());
+
+        let extract = |seed| -> Result<Vec<SpanId>, ParseError> {
+            let generator = SeededIdGenerator::new(seed);
+            let traceparent = b"00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01";
+            let context = TraceContext::from_bytes_with(traceparent, &generator)?;
+            Ok(vec![
+                B3Propagator::default()
+                    .extract_with(&carrier, &generator)?
+                    .unwrap()
+                    .id(),
+                composite
+                    .extract_with(&carrier, &generator)?
+                    .unwrap()
+                    .1
+                    .id(),
+                TraceContext::extract_from_with(&carrier, &generator)?.id(),
+                TraceContext::from_binary_with(&context.to_binary(), &generator)?.id(),
+                context.id(),
+            ])
+        };
+        assert_eq!(extract(3)?, extract(3)?);
+        assert_ne!(extract(3)?, extract(4)?);
+        Ok(())
+    }
+
+    #[test]
+    fn random_differs() {
+        let generator = RandomIdGenerator::new();
+        assert_ne!(generator.new_trace_id(), generator.new_trace_id());
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{hex_field, ParseError};
+use rand::Rng;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The 16 byte id of a whole trace.
+///
+/// ## Examples
+/// ```
+/// let trace_id: trace_context::TraceId = "0af7651916cd43dd8448eb211c80319c".parse().unwrap();
+///
+/// assert_eq!(trace_id.to_string(), "0af7651916cd43dd8448eb211c80319c");
+/// assert_eq!(trace_id.to_bytes()[0], 0x0a);
+/// assert!(trace_id.is_valid());
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TraceId(u128);
+
+impl TraceId {
+    /// Create a TraceId from its big-endian byte representation.
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        TraceId(u128::from_be_bytes(bytes))
+    }
+
+    /// Return the big-endian byte representation of the TraceId.
+    pub fn to_bytes(self) -> [u8; 16] {
+        self.0.to_be_bytes()
+    }
+
+    /// Returns false if the TraceId is all zeros, which the spec considers invalid.
+    pub fn is_valid(self) -> bool {
+        self.0 != 0
+    }
+}
+
+impl From<u128> for TraceId {
+    fn from(id: u128) -> Self {
+        TraceId(id)
+    }
+}
+
+impl From<TraceId> for u128 {
+    fn from(id: TraceId) -> Self {
+        id.0
+    }
+}
+
+impl FromStr for TraceId {
+    type Err = ParseError;
+
+    /// Parse a TraceId from exactly 32 lowercase hex characters.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        hex_field(Some(s.as_bytes()), "trace-id", 32).map(TraceId)
+    }
+}
+
+impl fmt::Display for TraceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl fmt::LowerHex for TraceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Debug for TraceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("TraceId")
+            .field(&format_args!("{}", self))
+            .finish()
+    }
+}
+
+/// How the trace id of a new root TraceContext is generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceIdFormat {
+    /// All 128 bits are random.
+    Random,
+    /// The upper 32 bits hold the current unix time in seconds and the remaining 96 bits are
+    /// random. AWS X-Ray rejects trace ids that don't follow this format.
+    TimePrefixed,
+}
+
+impl TraceIdFormat {
+    /// Generate a new trace id in this format.
+    ///
+    /// The random part is never all zeros: a zero draw is remapped to 1, so the trace id is
+    /// always valid.
+    pub(crate) fn generate<R: Rng + ?Sized>(self, rng: &mut R) -> TraceId {
+        match self {
+            TraceIdFormat::Random => TraceId(rng.gen::<u128>().max(1)),
+            TraceIdFormat::TimePrefixed => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_secs())
+                    .unwrap_or(0);
+                let random = (rng.gen::<u128>() & ((1 << 96) - 1)).max(1);
+                TraceId(u128::from(now as u32) << 96 | random)
+            }
+        }
+    }
+}
+
+/// The 8 byte id of a single span, also used as the parent-id of its children.
+///
+/// ## Examples
+/// ```
+/// let span_id: trace_context::SpanId = "00f067aa0ba902b7".parse().unwrap();
+///
+/// assert_eq!(span_id.to_string(), "00f067aa0ba902b7");
+/// assert_eq!(span_id.to_bytes()[1], 0xf0);
+/// assert!(span_id.is_valid());
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SpanId(u64);
+
+impl SpanId {
+    /// Generate a new random span id. A zero draw is remapped to 1, so the id is always valid.
+    pub(crate) fn generate<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        SpanId(rng.gen::<u64>().max(1))
+    }
+
+    /// Create a SpanId from its big-endian byte representation.
+    pub fn from_bytes(bytes: [u8; 8]) -> Self {
+        SpanId(u64::from_be_bytes(bytes))
+    }
+
+    /// Return the big-endian byte representation of the SpanId.
+    pub fn to_bytes(self) -> [u8; 8] {
+        self.0.to_be_bytes()
+    }
+
+    /// Returns false if the SpanId is all zeros, which the spec considers invalid.
+    pub fn is_valid(self) -> bool {
+        self.0 != 0
+    }
+}
+
+impl From<u64> for SpanId {
+    fn from(id: u64) -> Self {
+        SpanId(id)
+    }
+}
+
+impl From<SpanId> for u64 {
+    fn from(id: SpanId) -> Self {
+        id.0
+    }
+}
+
+impl FromStr for SpanId {
+    type Err = ParseError;
+
+    /// Parse a SpanId from exactly 16 lowercase hex characters.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        hex_field(Some(s.as_bytes()), "span-id", 16).map(|id| SpanId(id as u64))
+    }
+}
+
+impl fmt::Display for SpanId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl fmt::LowerHex for SpanId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Debug for SpanId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("SpanId")
+            .field(&format_args!("{}", self))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SpanId, TraceId, TraceIdFormat};
+    use crate::ParseError;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn trace_id_bytes() {
+        let id = TraceId::from(0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10);
+        let bytes = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+        assert_eq!(id.to_bytes(), bytes);
+        assert_eq!(TraceId::from_bytes(bytes), id);
+    }
+
+    #[test]
+    fn span_id_bytes() {
+        let id = SpanId::from(0x0102_0304_0506_0708);
+        assert_eq!(id.to_bytes(), [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(SpanId::from_bytes([1, 2, 3, 4, 5, 6, 7, 8]), id);
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(
+            TraceId::from(1).to_string(),
+            "00000000000000000000000000000001"
+        );
+        assert_eq!(format!("{:x}", SpanId::from(0xab)), "00000000000000ab");
+        assert_eq!(
+            format!("{:?}", SpanId::from(0xab)),
+            "SpanId(00000000000000ab)"
+        );
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "0000000000000000000000000000000a".parse::<TraceId>(),
+            Ok(TraceId::from(10))
+        );
+        assert_eq!(
+            "000000000000000A".parse::<SpanId>(),
+            Err(ParseError::NotLowercaseHex("span-id"))
+        );
+        assert_eq!(
+            "0a".parse::<TraceId>(),
+            Err(ParseError::BadLength("trace-id"))
+        );
+    }
+
+    #[test]
+    fn is_valid() {
+        assert!(!TraceId::default().is_valid());
+        assert!(!SpanId::from(0).is_valid());
+        assert!(SpanId::from(1).is_valid());
+    }
+
+    #[test]
+    fn time_prefixed() {
+        let before = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let id = TraceIdFormat::TimePrefixed.generate(&mut rand::thread_rng());
+        let after = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let epoch = u64::from((u128::from(id) >> 96) as u32);
+        assert!(before <= epoch && epoch <= after);
+    }
+}
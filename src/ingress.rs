@@ -0,0 +1,215 @@
+use crate::{SpanId, TraceContext, TraceFlags, TraceId};
+
+/// How much to trust the `traceparent` header of requests crossing a trust boundary, such as
+/// requests from the internet reaching an edge service.
+///
+/// Pass it to
+/// [`TraceContext::extract_with_policy`](struct.TraceContext.html#method.extract_with_policy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IngressPolicy {
+    /// Continue the inbound trace as is.
+    Trust,
+    /// Discard the inbound trace and start a new root.
+    RestartTrace,
+    /// Start a new root, but keep the inbound trace as a [`Link`](struct.Link.html).
+    RestartButLink,
+    /// Keep the inbound ids, but decide locally whether the trace is sampled.
+    IgnoreSampledFlag,
+}
+
+/// What [`TraceContext::extract_with_policy`](struct.TraceContext.html#method.extract_with_policy)
+/// did with the inbound trace, so it can be logged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IngressDecision {
+    /// The request had no `traceparent` header, so a new root was started.
+    Root,
+    /// The inbound trace was continued as is.
+    Trusted,
+    /// The inbound trace was discarded and a new root started. This is also the outcome of an
+    /// invalid `traceparent` header with the restart policies.
+    Restarted,
+    /// A new root was started, and the inbound trace kept as a link.
+    RestartedWithLink(Link),
+    /// The inbound ids were kept, but the sampled flag was decided locally.
+    SampledFlagIgnored {
+        /// The sampled flag of the inbound header.
+        inbound_sampled: bool,
+    },
+}
+
+/// A reference to an inbound trace that wasn't continued.
+///
+/// Record it as a span link so the restarted trace can still be correlated with the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Link {
+    trace_id: TraceId,
+    span_id: SpanId,
+    flags: TraceFlags,
+}
+
+impl Link {
+    pub(crate) fn new(context: &TraceContext) -> Option<Self> {
+        Some(Self {
+            trace_id: context.trace_id(),
+            span_id: context.parent_id()?,
+            flags: context.flags(),
+        })
+    }
+
+    /// Return the trace id of the linked trace.
+    pub fn trace_id(&self) -> TraceId {
+        self.trace_id
+    }
+
+    /// Return the id of the linked span.
+    pub fn span_id(&self) -> SpanId {
+        self.span_id
+    }
+
+    /// Return the trace flags of the linked span.
+    pub fn flags(&self) -> TraceFlags {
+        self.flags
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IngressDecision, IngressPolicy};
+    use crate::{AlwaysOff, AlwaysOn, ParseError, TraceContext, TraceFlags, TraceIdRatio};
+
+    const TRACEPARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01";
+
+    fn headers(traceparent: &str) -> http::HeaderMap {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("traceparent", traceparent.parse().unwrap());
+        headers.insert("tracestate", "acme=1".parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn trust() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let (context, decision) = TraceContext::extract_with_policy(
+            &headers(TRACEPARENT),
+            IngressPolicy::Trust,
+            &AlwaysOff,
+        )?;
+        assert_eq!(decision, IngressDecision::Trusted);
+        assert_eq!(context.parent_id(), Some("00f067aa0ba902b7".parse()?));
+        assert!(context.sampled());
+        assert_eq!(context.trace_state().get("acme"), Some("1"));
+        Ok(())
+    }
+
+    #[test]
+    fn root() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        for &policy in &[
+            IngressPolicy::Trust,
+            IngressPolicy::RestartTrace,
+            IngressPolicy::RestartButLink,
+            IngressPolicy::IgnoreSampledFlag,
+        ] {
+            let (context, decision) =
+                TraceContext::extract_with_policy(&http::HeaderMap::new(), policy, &AlwaysOff)?;
+            assert_eq!(decision, IngressDecision::Root);
+            assert_eq!(context.parent_id(), None);
+            assert!(!context.sampled());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn restart() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let (context, decision) = TraceContext::extract_with_policy(
+            &headers(TRACEPARENT),
+            IngressPolicy::RestartTrace,
+            &AlwaysOff,
+        )?;
+        assert_eq!(decision, IngressDecision::Restarted);
+        assert_ne!(
+            context.trace_id(),
+            "0af7651916cd43dd8448eb211c80319c".parse()?
+        );
+        assert_eq!(context.parent_id(), None);
+        assert!(!context.sampled());
+        assert!(context.trace_state().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn restart_but_link() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let (context, decision) = TraceContext::extract_with_policy(
+            &headers(TRACEPARENT),
+            IngressPolicy::RestartButLink,
+            &AlwaysOn,
+        )?;
+        let link = match decision {
+            IngressDecision::RestartedWithLink(link) => link,
+            decision => panic!("unexpected decision {:?}", decision),
+        };
+        assert_eq!(link.trace_id(), "0af7651916cd43dd8448eb211c80319c".parse()?);
+        assert_eq!(link.span_id(), "00f067aa0ba902b7".parse()?);
+        assert_eq!(link.flags(), TraceFlags::SAMPLED);
+        assert_ne!(context.trace_id(), link.trace_id());
+        assert_eq!(context.parent_id(), None);
+
+        let (_, decision) = TraceContext::extract_with_policy(
+            &headers("00-0af7"),
+            IngressPolicy::RestartButLink,
+            &AlwaysOn,
+        )?;
+        assert_eq!(decision, IngressDecision::Restarted);
+        Ok(())
+    }
+
+    #[test]
+    fn ignore_sampled_flag() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let (context, decision) = TraceContext::extract_with_policy(
+            &headers(TRACEPARENT),
+            IngressPolicy::IgnoreSampledFlag,
+            &AlwaysOff,
+        )?;
+        assert_eq!(
+            decision,
+            IngressDecision::SampledFlagIgnored {
+                inbound_sampled: true
+            }
+        );
+        assert_eq!(context.parent_id(), Some("00f067aa0ba902b7".parse()?));
+        assert!(!context.sampled());
+
+        assert_eq!(
+            TraceContext::extract_with_policy(
+                &headers("00-0af7"),
+                IngressPolicy::IgnoreSampledFlag,
+                &AlwaysOff
+            )
+            .unwrap_err(),
+            ParseError::BadLength("trace-id")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn ignore_chosen_trace_id() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        // The lower 64 bits of the trace id would always be sampled by TraceIdRatio.
+        let headers = headers("00-ffffffffffffffff0000000000000001-00f067aa0ba902b7-00");
+        let sampler = TraceIdRatio::new(0.001);
+        let mut sampled = 0;
+        for _ in 0..1000 {
+            let (context, _) = TraceContext::extract_with_policy(
+                &headers,
+                IngressPolicy::IgnoreSampledFlag,
+                &sampler,
+            )?;
+            assert_eq!(
+                context.trace_id(),
+                "ffffffffffffffff0000000000000001".parse()?
+            );
+            if context.sampled() {
+                sampled += 1;
+            }
+        }
+        assert!(sampled < 20, "sampled {}", sampled);
+        Ok(())
+    }
+}
@@ -0,0 +1,306 @@
+use crate::baggage::{percent_decode, Encoded};
+use crate::{
+    Baggage, Extractor, IdGenerator, Injector, ParseError, Propagator, RandomIdGenerator, SpanId,
+    TraceContext, TraceFlags, TraceId,
+};
+
+const HEADER: &str = "uber-trace-id";
+const BAGGAGE_PREFIX: &str = "uberctx-";
+
+const FLAG_SAMPLED: u8 = 0b01;
+const FLAG_DEBUG: u8 = 0b10;
+
+/// Converts between [Jaeger](https://www.jaegertracing.io/docs/client-libraries/#propagation-format)
+/// `uber-trace-id` and `uberctx-*` headers and TraceContext and Baggage.
+///
+/// ## Examples
+/// ```
+/// use trace_context::JaegerPropagator;
+///
+/// let mut headers = http::HeaderMap::new();
+/// headers.insert("uber-trace-id", "a3ce929d0e0e4736:f067aa0ba902b7:0:1".parse().unwrap());
+///
+/// let context = JaegerPropagator::new().extract(&headers).unwrap().unwrap();
+///
+/// assert_eq!(context.trace_id().to_string(), "0000000000000000a3ce929d0e0e4736");
+/// assert_eq!(context.parent_id().unwrap().to_string(), "00f067aa0ba902b7");
+/// assert!(context.sampled());
+///
+/// // Continue the trace towards a W3C service.
+/// let mut output = http::HeaderMap::new();
+/// context.child().inject(&mut output);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JaegerPropagator;
+
+impl JaegerPropagator {
+    /// Create a JaegerPropagator.
+    pub fn new() -> Self {
+        JaegerPropagator
+    }
+
+    /// Create and return a TraceContext based on the `uber-trace-id` header of the carrier.
+    ///
+    /// Trace and span ids may be up to 32 and 16 lowercase hex characters long respectively,
+    /// without zero padding. A header with URL-encoded `%3A` separators is accepted as well. Returns
+    /// `Ok(None)` if the carrier has no `uber-trace-id` header.
+    pub fn extract<E: Extractor + ?Sized>(
+        &self,
+        carrier: &E,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        self.extract_with(carrier, &RandomIdGenerator::new())
+    }
+
+    /// Create and return a TraceContext like [`extract`](#method.extract), using `gen` to
+    /// generate its id.
+    pub fn extract_with<E: Extractor + ?Sized, G: IdGenerator + ?Sized>(
+        &self,
+        carrier: &E,
+        gen: &G,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        let header = match carrier.get(HEADER) {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+
+        let decoded;
+        let header = if header.contains(':') {
+            header
+        } else {
+            decoded = percent_decode(header);
+            &decoded
+        };
+
+        let mut parts = header.split(':');
+        let trace_id = TraceId::from(hex(parts.next(), "trace-id", 32)?);
+        let span_id = SpanId::from(hex(parts.next(), "span-id", 16)? as u64);
+        hex(parts.next(), "parent-span-id", 16)?;
+        let flags = hex(parts.next(), "flags", 2)? as u8;
+        if parts.next().is_some() {
+            return Err(ParseError::BadLength(HEADER));
+        }
+
+        if !trace_id.is_valid() {
+            return Err(ParseError::ZeroTraceId);
+        }
+        if !span_id.is_valid() {
+            return Err(ParseError::ZeroParentId);
+        }
+
+        let mut context = TraceContext::remote(gen, trace_id, span_id, TraceFlags::default());
+        context.set_sampled(flags & FLAG_SAMPLED != 0);
+        context.set_debug(flags & FLAG_DEBUG != 0);
+        Ok(Some(context))
+    }
+
+    /// Add the `uber-trace-id` header for the TraceContext to the carrier.
+    pub fn inject<I: Injector + ?Sized>(&self, context: &TraceContext, carrier: &mut I) {
+        let mut flags = 0;
+        if context.sampled() {
+            flags |= FLAG_SAMPLED;
+        }
+        if context.debug() {
+            flags |= FLAG_DEBUG;
+        }
+        let parent_id = context.parent_id().map(u64::from).unwrap_or(0);
+
+        carrier.set(
+            HEADER,
+            format!(
+                "{:x}:{:x}:{:x}:{:x}",
+                u128::from(context.trace_id()),
+                u64::from(context.id()),
+                parent_id,
+                flags
+            ),
+        );
+    }
+
+    /// Create and return a Baggage object based on the `uberctx-*` headers of the carrier.
+    ///
+    /// Values are URL-decoded. Entries whose key isn't a valid baggage key are skipped.
+    ///
+    /// ## Examples
+    /// ```
+    /// let mut headers = http::HeaderMap::new();
+    /// headers.insert("uberctx-tenant", "acme%20corp".parse().unwrap());
+    ///
+    /// let baggage = trace_context::JaegerPropagator::new().extract_baggage(&headers);
+    ///
+    /// assert_eq!(baggage.get("tenant"), Some("acme corp"));
+    /// ```
+    pub fn extract_baggage<E: Extractor + ?Sized>(&self, carrier: &E) -> Baggage {
+        let mut baggage = Baggage::new();
+        for key in carrier.keys() {
+            if !key.starts_with(BAGGAGE_PREFIX) {
+                continue;
+            }
+            if let Some(value) = carrier.get(key) {
+                let _ = baggage.insert(&key[BAGGAGE_PREFIX.len()..], &percent_decode(value));
+            }
+        }
+        baggage
+    }
+
+    /// Add an `uberctx-*` header to the carrier for every list-member of the Baggage.
+    pub fn inject_baggage<I: Injector + ?Sized>(&self, baggage: &Baggage, carrier: &mut I) {
+        for (key, value) in baggage.iter() {
+            carrier.set(
+                &format!("{}{}", BAGGAGE_PREFIX, key),
+                Encoded(value).to_string(),
+            );
+        }
+    }
+}
+
+impl Propagator for JaegerPropagator {
+    fn name(&self) -> &'static str {
+        "jaeger"
+    }
+
+    fn extract_with(
+        &self,
+        carrier: &dyn Extractor,
+        gen: &dyn IdGenerator,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        JaegerPropagator::extract_with(self, carrier, gen)
+    }
+
+    fn inject(&self, context: &TraceContext, carrier: &mut dyn Injector) {
+        JaegerPropagator::inject(self, context, carrier)
+    }
+}
+
+/// Parse a non-padded lowercase hex field of at most `max_len` characters.
+fn hex(part: Option<&str>, name: &'static str, max_len: usize) -> Result<u128, ParseError> {
+    let part = part.ok_or(ParseError::MissingField(name))?;
+    if part.is_empty() || part.len() > max_len {
+        return Err(ParseError::BadLength(name));
+    }
+    if !part.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return Err(ParseError::NotLowercaseHex(name));
+    }
+    Ok(u128::from_str_radix(part, 16).unwrap())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{JaegerPropagator, HEADER};
+    use crate::carrier::test::carrier;
+    use crate::{Baggage, ParseError, SpanId, TraceId};
+    use std::collections::HashMap;
+
+    #[test]
+    fn extract() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let context = JaegerPropagator::new()
+            .extract(&carrier(&[(
+                HEADER,
+                "80f198ee56343ba864fe8b2a57d3eff7:e457b5a2e4d86bd1:5e3ac9a4f6e3b90:1",
+            )]))?
+            .unwrap();
+        assert_eq!(
+            context.trace_id(),
+            "80f198ee56343ba864fe8b2a57d3eff7".parse()?
+        );
+        assert_eq!(context.parent_id(), Some("e457b5a2e4d86bd1".parse()?));
+        assert!(context.sampled());
+        assert!(!context.debug());
+        Ok(())
+    }
+
+    #[test]
+    fn short_ids() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let context = JaegerPropagator::new()
+            .extract(&carrier(&[(HEADER, "abc:1f:0:0")]))?
+            .unwrap();
+        assert_eq!(context.trace_id(), TraceId::from(0xabc));
+        assert_eq!(context.parent_id(), Some(SpanId::from(0x1f)));
+        assert!(!context.sampled());
+        Ok(())
+    }
+
+    #[test]
+    fn url_encoded() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let context = JaegerPropagator::new()
+            .extract(&carrier(&[(HEADER, "abc%3A1f%3a0%3A3")]))?
+            .unwrap();
+        assert_eq!(context.trace_id(), TraceId::from(0xabc));
+        assert!(context.sampled());
+        assert!(context.debug());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        let jaeger = JaegerPropagator::new();
+        assert_eq!(
+            jaeger
+                .extract(&carrier(&[(HEADER, "abc:1f:0")]))
+                .unwrap_err(),
+            ParseError::MissingField("flags")
+        );
+        assert_eq!(
+            jaeger
+                .extract(&carrier(&[(HEADER, "0:1f:0:1")]))
+                .unwrap_err(),
+            ParseError::ZeroTraceId
+        );
+        assert_eq!(
+            jaeger
+                .extract(&carrier(&[(HEADER, "abc:00f067aa0ba902b7a:0:1")]))
+                .unwrap_err(),
+            ParseError::BadLength("span-id")
+        );
+        assert_eq!(
+            jaeger
+                .extract(&carrier(&[(HEADER, "xyz:1f:0:1")]))
+                .unwrap_err(),
+            ParseError::NotLowercaseHex("trace-id")
+        );
+        assert_eq!(
+            jaeger
+                .extract(&carrier(&[(HEADER, "abc:1F:0:1")]))
+                .unwrap_err(),
+            ParseError::NotLowercaseHex("span-id")
+        );
+    }
+
+    #[test]
+    fn inject() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let parent = JaegerPropagator::new()
+            .extract(&carrier(&[(HEADER, "abc:1f:0:3")]))?
+            .unwrap();
+        let mut carrier = HashMap::new();
+        JaegerPropagator::new().inject(&parent, &mut carrier);
+        assert_eq!(
+            carrier["uber-trace-id"],
+            format!("abc:{:x}:1f:3", u64::from(parent.id()))
+        );
+
+        let child = JaegerPropagator::new().extract(&carrier)?.unwrap();
+        assert_eq!(child.trace_id(), parent.trace_id());
+        assert_eq!(child.parent_id(), Some(parent.id()));
+        assert!(child.debug());
+        Ok(())
+    }
+
+    #[test]
+    fn baggage() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("uberctx-tenant", "acme%20corp".parse()?);
+        headers.insert("uberctx-cohort", "beta".parse()?);
+        headers.insert("uber-trace-id", "abc:1f:0:1".parse()?);
+        let baggage = JaegerPropagator::new().extract_baggage(&headers);
+        assert_eq!(baggage.len(), 2);
+        assert_eq!(baggage.get("tenant"), Some("acme corp"));
+
+        let mut carrier = HashMap::new();
+        JaegerPropagator::new().inject_baggage(&baggage, &mut carrier);
+        assert_eq!(carrier["uberctx-tenant"], "acme%20corp");
+        assert_eq!(carrier["uberctx-cohort"], "beta");
+
+        let baggage: Baggage = JaegerPropagator::new().extract_baggage(&carrier);
+        assert_eq!(baggage.get("tenant"), Some("acme corp"));
+        Ok(())
+    }
+}
@@ -10,8 +10,8 @@
 //!
 //! let context = trace_context::TraceContext::extract(&headers).unwrap();
 //!
-//! let trace_id = u128::from_str_radix("0af7651916cd43dd8448eb211c80319c", 16);
-//! let parent_id = u64::from_str_radix("00f067aa0ba902b7", 16);
+//! let trace_id = "0af7651916cd43dd8448eb211c80319c".parse();
+//! let parent_id = "00f067aa0ba902b7".parse();
 //!
 //! assert_eq!(context.trace_id(), trace_id.unwrap());
 //! assert_eq!(context.parent_id(), parent_id.ok());
@@ -20,22 +20,100 @@
 
 #![deny(unsafe_code)]
 
-use rand::Rng;
+mod b3;
+mod baggage;
+mod carrier;
+mod composite;
+mod consistent;
+mod datadog;
+mod error;
+mod flags;
+mod gcp;
+mod generator;
+mod id;
+mod ingress;
+mod jaeger;
+mod limits;
+mod propagator;
+mod rate_limiting;
+mod response;
+mod sampler;
+mod trace_state;
+mod vendor;
+mod xray;
+
+pub use b3::{B3Encoding, B3Propagator};
+pub use baggage::{Baggage, BaggageProperty};
+pub use carrier::{Extractor, Injector};
+pub use composite::{CompositePropagator, Conflict};
+pub use consistent::ConsistentProbability;
+pub use datadog::DatadogPropagator;
+pub use error::ParseError;
+pub use flags::{TraceFlags, UnknownFlags};
+pub use gcp::GcpPropagator;
+pub use generator::{IdGenerator, RandomIdGenerator, SeededIdGenerator};
+pub use id::{SpanId, TraceId, TraceIdFormat};
+pub use ingress::{IngressDecision, IngressPolicy, Link};
+pub use jaeger::JaegerPropagator;
+pub use limits::ExtractLimits;
+pub use propagator::{Propagator, TraceContextPropagator};
+pub use rate_limiting::{Clock, RateLimiting, SystemClock};
+pub use response::TraceResponse;
+pub use sampler::{AlwaysOff, AlwaysOn, ParentBased, Sampler, SamplingDecision, TraceIdRatio};
+pub use trace_state::{TraceState, TruncationPolicy};
+pub use vendor::VendorFields;
+pub use xray::XRayPropagator;
+
+use std::convert::TryInto;
 use std::fmt;
 
+/// The version of the Trace Context spec this crate implements and emits.
+const SUPPORTED_VERSION: u8 = 0;
+
+/// The length of a version `00` `traceparent` header.
+pub const TRACEPARENT_LEN: usize = 55;
+
+/// The length of the binary format written by `to_binary`.
+pub const BINARY_LEN: usize = 29;
+
+// The field ids of the binary format.
+const BINARY_TRACE_ID: u8 = 0;
+const BINARY_SPAN_ID: u8 = 1;
+const BINARY_OPTIONS: u8 = 2;
+
 /// A TraceContext object
 #[derive(Debug)]
 pub struct TraceContext {
-    id: u64,
+    id: SpanId,
     version: u8,
-    trace_id: u128,
-    parent_id: Option<u64>,
-    flags: u8,
+    trace_id: TraceId,
+    parent_id: Option<SpanId>,
+    flags: TraceFlags,
+    extra_fields: bool,
+    trace_state: TraceState,
+    debug: bool,
+    deferred: Option<Deferral>,
+    vendor: VendorFields,
+}
+
+/// How an inbound header left the sampling decision to the receiver, so propagators can write
+/// it back the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Deferral {
+    /// The sampling field was omitted.
+    Absent,
+    /// The sampling field was explicitly deferred, like X-Ray's `Sampled=?`.
+    Explicit,
 }
 
 impl TraceContext {
     /// Create and return TraceContext object based on `traceparent` HTTP header.
     ///
+    /// If the `traceparent` header is valid, the `tracestate` header is parsed as well. Multiple
+    /// `tracestate` headers are combined into a single list. A `tracestate` header that can't be
+    /// parsed is discarded, as required by the spec. The size of the headers isn't bounded; use
+    /// [`extract_with_limits`](#method.extract_with_limits) for requests from untrusted clients.
+    ///
     /// ## Examples
     /// ```
     /// let mut headers = http::HeaderMap::new();
@@ -46,36 +124,372 @@ impl TraceContext {
     ///
     /// let context = trace_context::TraceContext::extract(&headers).unwrap();
     ///
-    /// let trace_id = u128::from_str_radix("0af7651916cd43dd8448eb211c80319c", 16);
-    /// let parent_id = u64::from_str_radix("00f067aa0ba902b7", 16);
+    /// let trace_id = "0af7651916cd43dd8448eb211c80319c".parse();
+    /// let parent_id = "00f067aa0ba902b7".parse();
     ///
     /// assert_eq!(context.trace_id(), trace_id.unwrap());
     /// assert_eq!(context.parent_id(), parent_id.ok());
     /// assert_eq!(context.sampled(), true);
     /// ```
-    pub fn extract(headers: &http::HeaderMap) -> Result<Self, std::num::ParseIntError> {
-        let mut rng = rand::thread_rng();
+    pub fn extract(headers: &http::HeaderMap) -> Result<Self, ParseError> {
+        Self::extract_with(headers, &RandomIdGenerator::new())
+    }
 
+    /// Create and return TraceContext object based on `traceparent` HTTP header, using `gen`
+    /// to generate ids.
+    ///
+    /// ## Examples
+    /// ```
+    /// use trace_context::{SeededIdGenerator, TraceContext};
+    ///
+    /// let headers = http::HeaderMap::new();
+    ///
+    /// let a = TraceContext::extract_with(&headers, &SeededIdGenerator::new(1)).unwrap();
+    /// let b = TraceContext::extract_with(&headers, &SeededIdGenerator::new(1)).unwrap();
+    ///
+    /// assert_eq!(a.trace_id(), b.trace_id());
+    /// ```
+    pub fn extract_with<G: IdGenerator + ?Sized>(
+        headers: &http::HeaderMap,
+        gen: &G,
+    ) -> Result<Self, ParseError> {
         let traceparent = match headers.get("traceparent") {
-            Some(header) => header.to_str().unwrap(),
-            None => return Ok(Self::new_root()),
+            Some(header) => header.as_bytes(),
+            None => return Ok(Self::new_root_with(gen)),
         };
 
-        let parts: Vec<&str> = traceparent.split('-').collect();
+        let mut context = Self::parse(traceparent, gen)?;
 
-        Ok(Self {
-            id: rng.gen(),
-            version: u8::from_str_radix(parts[0], 16)?,
-            trace_id: u128::from_str_radix(parts[1], 16)?,
-            parent_id: Some(u64::from_str_radix(parts[2], 16)?),
-            flags: u8::from_str_radix(parts[3], 16)?,
-        })
+        if headers.contains_key("tracestate") {
+            let trace_state: Result<Vec<&str>, _> = headers
+                .get_all("tracestate")
+                .iter()
+                .map(|value| value.to_str())
+                .collect();
+            context.trace_state = match trace_state {
+                Ok(trace_state) => trace_state.join(",").parse().unwrap_or_default(),
+                Err(_) => TraceState::new(),
+            };
+        }
+
+        Ok(context)
+    }
+
+    /// Create and return TraceContext object based on `traceparent` HTTP header, after checking
+    /// the headers against `limits`.
+    ///
+    /// Unlike `extract`, which reads the first `traceparent` header and discards an invalid
+    /// `tracestate`, a violated limit results in an error. Use this for requests from
+    /// untrusted clients.
+    ///
+    /// ## Examples
+    /// ```
+    /// use trace_context::{ExtractLimits, ParseError, TraceContext};
+    ///
+    /// let traceparent = "00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01";
+    /// let mut headers = http::HeaderMap::new();
+    /// headers.append("traceparent", traceparent.parse().unwrap());
+    /// headers.append("traceparent", traceparent.parse().unwrap());
+    ///
+    /// assert_eq!(
+    ///     TraceContext::extract_with_limits(&headers, &ExtractLimits::new()).unwrap_err(),
+    ///     ParseError::DuplicateTraceparent,
+    /// );
+    /// ```
+    pub fn extract_with_limits(
+        headers: &http::HeaderMap,
+        limits: &ExtractLimits,
+    ) -> Result<Self, ParseError> {
+        limits.check(headers)?;
+        Self::extract(headers)
+    }
+
+    /// Create and return TraceContext object based on `traceparent` HTTP header, using `sampler`
+    /// to decide whether a new root is sampled when the header is absent.
+    ///
+    /// When the header is present, its sampled flag is kept. As with
+    /// [`new_root_with_sampler`](#method.new_root_with_sampler), ids are generated randomly.
+    ///
+    /// ## Examples
+    /// ```
+    /// use trace_context::{TraceContext, TraceIdRatio};
+    ///
+    /// let sampler = TraceIdRatio::new(0.0);
+    /// let context = TraceContext::extract_with_sampler(&http::HeaderMap::new(), &sampler).unwrap();
+    ///
+    /// assert_eq!(context.sampled(), false);
+    /// ```
+    pub fn extract_with_sampler<S: Sampler + ?Sized>(
+        headers: &http::HeaderMap,
+        sampler: &S,
+    ) -> Result<Self, ParseError> {
+        if !headers.contains_key("traceparent") {
+            return Ok(Self::new_root_with_sampler(sampler));
+        }
+        Self::extract(headers)
+    }
+
+    /// Create and return TraceContext object based on `traceparent` HTTP header, applying an
+    /// ingress policy for requests crossing a trust boundary, and return what was decided.
+    ///
+    /// `sampler` decides whether new roots are sampled, and whether the inbound trace is
+    /// sampled with [`IngressPolicy::IgnoreSampledFlag`](enum.IngressPolicy.html). In the
+    /// latter case the sampler is passed a random trace id rather than the inbound one, so
+    /// clients can't influence the decision. With the restart policies, an invalid
+    /// `traceparent` header results in a new root rather than an error.
+    ///
+    /// To also bound the size of the headers, check them with
+    /// [`ExtractLimits::check`](struct.ExtractLimits.html#method.check) first.
+    ///
+    /// ## Examples
+    /// ```
+    /// use trace_context::{IngressDecision, IngressPolicy, TraceContext, TraceIdRatio};
+    ///
+    /// let mut headers = http::HeaderMap::new();
+    /// headers.insert(
+    ///     "traceparent",
+    ///     "00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01".parse().unwrap(),
+    /// );
+    ///
+    /// let (context, decision) = TraceContext::extract_with_policy(
+    ///     &headers,
+    ///     IngressPolicy::RestartButLink,
+    ///     &TraceIdRatio::new(0.01),
+    /// ).unwrap();
+    ///
+    /// assert_eq!(context.parent_id(), None);
+    /// if let IngressDecision::RestartedWithLink(link) = decision {
+    ///     assert_eq!(link.trace_id().to_string(), "0af7651916cd43dd8448eb211c80319c");
+    /// }
+    /// ```
+    pub fn extract_with_policy<S: Sampler + ?Sized>(
+        headers: &http::HeaderMap,
+        policy: IngressPolicy,
+        sampler: &S,
+    ) -> Result<(Self, IngressDecision), ParseError> {
+        if !headers.contains_key("traceparent") {
+            return Ok((Self::new_root_with_sampler(sampler), IngressDecision::Root));
+        }
+
+        let inbound = Self::extract(headers);
+        let decision = match (policy, inbound) {
+            (IngressPolicy::Trust, inbound) => return Ok((inbound?, IngressDecision::Trusted)),
+            (IngressPolicy::IgnoreSampledFlag, inbound) => {
+                let mut context = inbound?;
+                let inbound_sampled = context.sampled();
+                // Decide from a local trace id, since clients can choose theirs to force
+                // samplers deciding from the trace id.
+                let local_trace_id = RandomIdGenerator::new().new_trace_id();
+                let decision = sampler.should_sample(local_trace_id, None);
+                context.set_sampled(decision.is_sampled());
+                return Ok((
+                    context,
+                    IngressDecision::SampledFlagIgnored { inbound_sampled },
+                ));
+            }
+            (IngressPolicy::RestartButLink, Ok(inbound)) => match Link::new(&inbound) {
+                Some(link) => IngressDecision::RestartedWithLink(link),
+                None => IngressDecision::Restarted,
+            },
+            (IngressPolicy::RestartTrace, _) | (IngressPolicy::RestartButLink, Err(_)) => {
+                IngressDecision::Restarted
+            }
+        };
+        Ok((Self::new_root_with_sampler(sampler), decision))
+    }
+
+    /// Create and return TraceContext object based on the `traceparent` and `tracestate` keys of
+    /// any carrier, such as message queue headers or RPC metadata.
+    ///
+    /// A missing `traceparent` key results in a new root TraceContext, and a `tracestate` value
+    /// that can't be parsed is discarded, just like with `extract`. Unlike `extract`, only a
+    /// single `tracestate` value is read. A `traceparent` value the carrier can't return as text,
+    /// such as a non-ASCII HTTP header, results in `ParseError::NonAscii`, as with `extract`.
+    ///
+    /// ## Examples
+    /// ```
+    /// let mut carrier = std::collections::HashMap::new();
+    /// carrier.insert(
+    ///     "traceparent".to_string(),
+    ///     "00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01".to_string(),
+    /// );
+    ///
+    /// let context = trace_context::TraceContext::extract_from(&carrier).unwrap();
+    ///
+    /// assert_eq!(context.trace_id().to_string(), "0af7651916cd43dd8448eb211c80319c");
+    /// ```
+    pub fn extract_from<E: Extractor + ?Sized>(carrier: &E) -> Result<Self, ParseError> {
+        Self::extract_from_with(carrier, &RandomIdGenerator::new())
+    }
+
+    /// Create and return TraceContext object like [`extract_from`](#method.extract_from), using
+    /// `gen` to generate ids.
+    pub fn extract_from_with<E: Extractor + ?Sized, G: IdGenerator + ?Sized>(
+        carrier: &E,
+        gen: &G,
+    ) -> Result<Self, ParseError> {
+        let traceparent = match carrier.get("traceparent") {
+            Some(traceparent) => traceparent,
+            None if carrier.contains_key("traceparent") => return Err(ParseError::NonAscii),
+            None => return Ok(Self::new_root_with(gen)),
+        };
+
+        let mut context = Self::parse(traceparent.as_bytes(), gen)?;
+
+        if let Some(trace_state) = carrier.get("tracestate") {
+            context.trace_state = trace_state.parse().unwrap_or_default();
+        }
+
+        Ok(context)
+    }
+
+    /// Parse a `traceparent` header value according to the W3C Trace Context Level 1 spec.
+    ///
+    /// Headers with a version higher than the one supported by this crate are parsed leniently:
+    /// only the first four fields are read and any trailing `-` delimited data is ignored.
+    ///
+    /// Parsing doesn't allocate, which makes this suitable for hot paths where the raw header
+    /// bytes are already at hand. The returned TraceContext has an empty TraceState.
+    ///
+    /// ## Examples
+    /// ```
+    /// let context = trace_context::TraceContext::from_bytes(
+    ///     b"00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01"
+    /// ).unwrap();
+    ///
+    /// assert_eq!(context.trace_id().to_string(), "0af7651916cd43dd8448eb211c80319c");
+    /// assert_eq!(context.sampled(), true);
+    /// ```
+    pub fn from_bytes(traceparent: &[u8]) -> Result<Self, ParseError> {
+        Self::from_bytes_with(traceparent, &RandomIdGenerator::new())
+    }
+
+    /// Parse a `traceparent` header value like [`from_bytes`](#method.from_bytes), using `gen`
+    /// to generate the id of the TraceContext.
+    pub fn from_bytes_with<G: IdGenerator + ?Sized>(
+        traceparent: &[u8],
+        gen: &G,
+    ) -> Result<Self, ParseError> {
+        Self::parse(traceparent, gen)
+    }
+
+    /// Parse the binary trace context format used by the OpenCensus `grpc-trace-bin` header.
+    ///
+    /// The format is a version byte followed by fields, each prefixed with its id: the trace-id
+    /// (`0`, 16 bytes), the span-id (`1`, 8 bytes) and the trace options (`2`, 1 byte). Fields
+    /// are read regardless of the version. Since the length of a field with an unknown id can't
+    /// be known, parsing stops at the first one and the remaining bytes are ignored. Missing
+    /// trace options leave the TraceContext unsampled.
+    ///
+    /// ## Examples
+    /// ```
+    /// let context = trace_context::TraceContext::from_bytes(
+    ///     b"00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01"
+    /// ).unwrap();
+    ///
+    /// let parsed = trace_context::TraceContext::from_binary(&context.to_binary()).unwrap();
+    ///
+    /// assert_eq!(parsed.trace_id(), context.trace_id());
+    /// assert_eq!(parsed.parent_id(), Some(context.id()));
+    /// assert_eq!(parsed.sampled(), true);
+    /// ```
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, ParseError> {
+        Self::from_binary_with(bytes, &RandomIdGenerator::new())
+    }
+
+    /// Parse the binary trace context format like [`from_binary`](#method.from_binary), using
+    /// `gen` to generate the id of the TraceContext.
+    pub fn from_binary_with<G: IdGenerator + ?Sized>(
+        bytes: &[u8],
+        gen: &G,
+    ) -> Result<Self, ParseError> {
+        let mut fields = match bytes.split_first() {
+            Some((_version, fields)) => fields,
+            None => return Err(ParseError::MissingField("version")),
+        };
+
+        let mut trace_id = None;
+        let mut span_id = None;
+        let mut flags = TraceFlags::default();
+        while let Some((&field, rest)) = fields.split_first() {
+            let (name, len) = match field {
+                BINARY_TRACE_ID => ("trace-id", 16),
+                BINARY_SPAN_ID => ("span-id", 8),
+                BINARY_OPTIONS => ("trace-options", 1),
+                _ => break,
+            };
+            if rest.len() < len {
+                return Err(ParseError::BadLength(name));
+            }
+            let (value, rest) = rest.split_at(len);
+            match field {
+                BINARY_TRACE_ID => {
+                    trace_id = Some(TraceId::from_bytes(value.try_into().unwrap()));
+                }
+                BINARY_SPAN_ID => span_id = Some(SpanId::from_bytes(value.try_into().unwrap())),
+                _ => flags = TraceFlags::from_bits(value[0]),
+            }
+            fields = rest;
+        }
+
+        let trace_id = trace_id.ok_or(ParseError::MissingField("trace-id"))?;
+        let span_id = span_id.ok_or(ParseError::MissingField("span-id"))?;
+        if !trace_id.is_valid() {
+            return Err(ParseError::ZeroTraceId);
+        }
+        if !span_id.is_valid() {
+            return Err(ParseError::ZeroParentId);
+        }
+
+        Ok(Self::remote(gen, trace_id, span_id, flags))
+    }
+
+    fn parse<G: IdGenerator + ?Sized>(traceparent: &[u8], gen: &G) -> Result<Self, ParseError> {
+        let fields = HeaderFields::parse(traceparent, "traceparent", "parent-id")?;
+
+        let mut context = Self::remote(gen, fields.trace_id, fields.span_id, fields.flags);
+        context.version = fields.version;
+        context.extra_fields = fields.extra_fields;
+        Ok(context)
+    }
+
+    /// Create a TraceContext continuing a trace received from a remote parent, using `gen` to
+    /// generate its id.
+    pub(crate) fn remote<G: IdGenerator + ?Sized>(
+        gen: &G,
+        trace_id: TraceId,
+        parent_id: SpanId,
+        flags: TraceFlags,
+    ) -> Self {
+        Self::new(generator::span_id(gen), trace_id, Some(parent_id), flags)
+    }
+
+    /// Create a sampled TraceContext without a parent for a trace id received from a remote
+    /// caller, using `gen` to generate its id.
+    pub(crate) fn remote_root<G: IdGenerator + ?Sized>(gen: &G, trace_id: TraceId) -> Self {
+        Self::new(generator::span_id(gen), trace_id, None, TraceFlags::SAMPLED)
+    }
+
+    fn new(id: SpanId, trace_id: TraceId, parent_id: Option<SpanId>, flags: TraceFlags) -> Self {
+        Self {
+            id,
+            version: SUPPORTED_VERSION,
+            trace_id,
+            parent_id,
+            flags,
+            extra_fields: false,
+            trace_state: TraceState::new(),
+            debug: false,
+            deferred: None,
+            vendor: VendorFields::default(),
+        }
     }
 
     /// Generate a new TraceContect object without a parent.
     ///
     /// By default root TraceContext objects are sampled.
-    /// To mark it unsampled, call `context.set_sampled(false)`.
+    /// To mark it unsampled, call `context.set_sampled(false)`, or use
+    /// [`new_root_with_sampler`](#method.new_root_with_sampler) to only sample some traces.
     ///
     /// ## Examples
     /// ```
@@ -85,18 +499,76 @@ impl TraceContext {
     /// assert_eq!(context.sampled(), true);
     /// ```
     pub fn new_root() -> Self {
-        let mut rng = rand::thread_rng();
+        Self::new_root_with_format(TraceIdFormat::Random)
+    }
 
-        Self {
-            id: rng.gen(),
-            version: 0,
-            trace_id: rng.gen(),
-            parent_id: None,
-            flags: 1,
-        }
+    /// Generate a new TraceContext object without a parent, using the given trace id format.
+    ///
+    /// Use [`TraceIdFormat::TimePrefixed`](enum.TraceIdFormat.html#variant.TimePrefixed) for
+    /// traces that pass through AWS services.
+    ///
+    /// ## Examples
+    /// ```
+    /// use trace_context::{TraceContext, TraceIdFormat};
+    ///
+    /// let context = TraceContext::new_root_with_format(TraceIdFormat::TimePrefixed);
+    /// let epoch = (u128::from(context.trace_id()) >> 96) as u64;
+    /// let now = std::time::SystemTime::now()
+    ///     .duration_since(std::time::UNIX_EPOCH)
+    ///     .unwrap()
+    ///     .as_secs();
+    ///
+    /// assert!(now - epoch < 5);
+    /// ```
+    pub fn new_root_with_format(format: TraceIdFormat) -> Self {
+        Self::new_root_with(&RandomIdGenerator::with_format(format))
+    }
+
+    /// Generate a new TraceContext object without a parent, using `gen` to generate ids.
+    ///
+    /// ## Examples
+    /// ```
+    /// use trace_context::{IdGenerator, SeededIdGenerator, TraceContext};
+    ///
+    /// let gen = SeededIdGenerator::new(1);
+    /// let context = TraceContext::new_root_with(&gen);
+    ///
+    /// let expected = SeededIdGenerator::new(1);
+    /// assert_eq!(context.id(), expected.new_span_id());
+    /// assert_eq!(context.trace_id(), expected.new_trace_id());
+    /// ```
+    pub fn new_root_with<G: IdGenerator + ?Sized>(gen: &G) -> Self {
+        let id = generator::span_id(gen);
+        Self::new(id, generator::trace_id(gen), None, TraceFlags::SAMPLED)
     }
 
-    /// Add the traceparent header to the http headers
+    /// Generate a new TraceContext object without a parent, using `sampler` to decide whether
+    /// it's sampled.
+    ///
+    /// Ids are generated randomly. To generate them with another
+    /// [`IdGenerator`](trait.IdGenerator.html), create the TraceContext with
+    /// [`new_root_with`](#method.new_root_with) and pass its trace id to the sampler.
+    ///
+    /// ## Examples
+    /// ```
+    /// use trace_context::{AlwaysOff, TraceContext};
+    ///
+    /// let context = TraceContext::new_root_with_sampler(&AlwaysOff);
+    ///
+    /// assert_eq!(context.sampled(), false);
+    /// ```
+    pub fn new_root_with_sampler<S: Sampler + ?Sized>(sampler: &S) -> Self {
+        let mut context = Self::new_root();
+        let decision = sampler.should_sample(context.trace_id, None);
+        context.set_sampled(decision.is_sampled());
+        context
+    }
+
+    /// Add the traceparent and tracestate headers to the http headers
+    ///
+    /// The header is always written using version `00` of the spec, regardless of the version
+    /// of the header this TraceContext was extracted from. The `tracestate` header is only
+    /// written if the TraceState isn't empty.
     ///
     /// ## Examples
     /// ```
@@ -119,49 +591,245 @@ impl TraceContext {
     /// assert_eq!(child.flags(), parent.flags());
     /// ```
     pub fn inject(&self, headers: &mut http::HeaderMap) {
-        headers.insert("traceparent", format!("{}", self).parse().unwrap());
+        let traceparent = http::header::HeaderValue::from_bytes(&self.to_bytes()).unwrap();
+        headers.insert("traceparent", traceparent);
+        if self.trace_state.is_empty() {
+            headers.remove("tracestate");
+        } else {
+            headers.insert("tracestate", self.trace_state.to_string().parse().unwrap());
+        }
+    }
+
+    /// Add the `traceresponse` header to http response headers, so the caller learns the trace
+    /// id, span id and sampling decision this TraceContext uses.
+    ///
+    /// Call this on the TraceContext of the server span, which may belong to a new trace if the
+    /// server restarted it. As with `inject`, the header is written using version `00`.
+    ///
+    /// ## Examples
+    /// ```
+    /// let context = trace_context::TraceContext::new_root();
+    ///
+    /// let mut response_headers = http::HeaderMap::new();
+    /// context.inject_response(&mut response_headers);
+    ///
+    /// let response = trace_context::TraceContext::extract_response(&response_headers)
+    ///     .unwrap()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(response.trace_id(), context.trace_id());
+    /// assert_eq!(response.child_id(), context.id());
+    /// assert_eq!(response.sampled(), true);
+    /// ```
+    pub fn inject_response(&self, headers: &mut http::HeaderMap) {
+        let traceresponse = http::header::HeaderValue::from_bytes(&self.to_bytes()).unwrap();
+        headers.insert("traceresponse", traceresponse);
+    }
+
+    /// Return the TraceResponse described by the `traceresponse` header of http response
+    /// headers, or `Ok(None)` if the server didn't send one.
+    pub fn extract_response(
+        headers: &http::HeaderMap,
+    ) -> Result<Option<TraceResponse>, ParseError> {
+        match headers.get("traceresponse") {
+            Some(traceresponse) => TraceResponse::from_bytes(traceresponse.as_bytes()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Add the `traceparent` and `tracestate` keys to any carrier, such as message queue headers
+    /// or RPC metadata.
+    ///
+    /// As with `inject`, `tracestate` is only written if the TraceState isn't empty.
+    ///
+    /// ## Examples
+    /// ```
+    /// let context = trace_context::TraceContext::new_root();
+    ///
+    /// let mut carrier = std::collections::BTreeMap::new();
+    /// context.inject_into(&mut carrier);
+    ///
+    /// assert_eq!(carrier["traceparent"], context.to_string());
+    /// ```
+    pub fn inject_into<I: Injector + ?Sized>(&self, carrier: &mut I) {
+        carrier.set("traceparent", self.to_string());
+        if !self.trace_state.is_empty() {
+            carrier.set("tracestate", self.trace_state.to_string());
+        }
+    }
+
+    /// Format the TraceContext as a version `00` `traceparent` header without allocating.
+    ///
+    /// ## Examples
+    /// ```
+    /// let context = trace_context::TraceContext::from_bytes(
+    ///     b"00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01"
+    /// ).unwrap();
+    /// let child = context.child();
+    ///
+    /// assert_eq!(&child.to_bytes()[..36], b"00-0af7651916cd43dd8448eb211c80319c-");
+    /// ```
+    pub fn to_bytes(&self) -> [u8; TRACEPARENT_LEN] {
+        let mut buf = [b'-'; TRACEPARENT_LEN];
+        write_hex(&mut buf[0..2], u128::from(SUPPORTED_VERSION));
+        write_hex(&mut buf[3..35], u128::from(self.trace_id));
+        write_hex(&mut buf[36..52], u128::from(u64::from(self.id)));
+        write_hex(&mut buf[53..55], u128::from(self.flags.bits()));
+        buf
+    }
+
+    /// Format the TraceContext in the binary format of the OpenCensus `grpc-trace-bin` header,
+    /// see [`from_binary`](#method.from_binary).
+    pub fn to_binary(&self) -> [u8; BINARY_LEN] {
+        let mut buf = [0; BINARY_LEN];
+        buf[0] = SUPPORTED_VERSION;
+        buf[1] = BINARY_TRACE_ID;
+        buf[2..18].copy_from_slice(&self.trace_id.to_bytes());
+        buf[18] = BINARY_SPAN_ID;
+        buf[19..27].copy_from_slice(&self.id.to_bytes());
+        buf[27] = BINARY_OPTIONS;
+        buf[28] = self.flags.bits();
+        buf
     }
 
     /// Generate a child of the current TraceContext and return it.
     ///
     /// The child will have a new randomly genrated `id` and its `parent_id` will be set to the
     /// `id` of this TraceContext.
+    ///
+    /// Trace flags not understood by this crate are cleared, as required by the spec. Use
+    /// [`TraceFlags::for_child`](struct.TraceFlags.html#method.for_child) together with
+    /// `set_flags` to apply a different policy.
     pub fn child(&self) -> Self {
-        let mut rng = rand::thread_rng();
+        self.child_with(&RandomIdGenerator::new())
+    }
 
+    /// Generate a child of the current TraceContext, using `gen` to generate its `id`.
+    pub fn child_with<G: IdGenerator + ?Sized>(&self, gen: &G) -> Self {
         Self {
-            id: rng.gen(),
-            version: self.version,
+            id: generator::span_id(gen),
+            version: SUPPORTED_VERSION,
             trace_id: self.trace_id,
             parent_id: Some(self.id),
-            flags: self.flags,
+            flags: self.flags.for_child(UnknownFlags::Clear),
+            extra_fields: false,
+            trace_state: self.trace_state.clone(),
+            debug: self.debug,
+            deferred: self.deferred,
+            vendor: self.vendor.clone(),
         }
     }
 
     /// Return the id of the TraceContext.
-    pub fn id(&self) -> u64 {
+    pub fn id(&self) -> SpanId {
         self.id
     }
 
     /// Return the version of the TraceContext spec used.
     ///
+    /// For an extracted TraceContext this is the version of the inbound `traceparent` header.
     /// You probably don't need this.
     pub fn version(&self) -> u8 {
         self.version
     }
 
+    /// Returns true if the inbound `traceparent` header carried fields beyond the four defined
+    /// by version `00` of the spec.
+    ///
+    /// This can only happen for headers using a future version of the spec, and can be used to
+    /// detect upstream services emitting a newer format.
+    ///
+    /// ## Examples
+    /// ```
+    /// let mut headers = http::HeaderMap::new();
+    /// headers.insert(
+    ///   "traceparent",
+    ///   "cc-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01-what-the-future-holds".parse().unwrap()
+    /// );
+    ///
+    /// let context = trace_context::TraceContext::extract(&headers).unwrap();
+    ///
+    /// assert_eq!(context.version(), 0xcc);
+    /// assert!(context.has_extra_fields());
+    /// ```
+    pub fn has_extra_fields(&self) -> bool {
+        self.extra_fields
+    }
+
     /// Return the trace id of the TraceContext.
     ///
     /// All children will have the same `trace_id`.
-    pub fn trace_id(&self) -> u128 {
+    pub fn trace_id(&self) -> TraceId {
         self.trace_id
     }
 
     /// Return the id of the parent TraceContext.
-    pub fn parent_id(&self) -> Option<u64> {
+    pub fn parent_id(&self) -> Option<SpanId> {
         self.parent_id
     }
 
+    /// Return the trace flags of the TraceContext.
+    pub fn flags(&self) -> TraceFlags {
+        self.flags
+    }
+
+    /// Replace the trace flags of the TraceContext.
+    ///
+    /// ## Examples
+    /// ```
+    /// use trace_context::{TraceContext, TraceFlags};
+    ///
+    /// let mut context = TraceContext::new_root();
+    /// context.set_flags(TraceFlags::SAMPLED | TraceFlags::RANDOM);
+    ///
+    /// assert!(context.flags().contains(TraceFlags::RANDOM));
+    /// ```
+    pub fn set_flags(&mut self, flags: TraceFlags) {
+        if flags.contains(TraceFlags::SAMPLED) != self.sampled() {
+            self.deferred = None;
+        }
+        if !flags.contains(TraceFlags::SAMPLED) {
+            self.debug = false;
+        }
+        self.flags = flags;
+    }
+
+    /// Return the vendor-specific trace data of the TraceContext.
+    ///
+    /// ## Examples
+    /// ```
+    /// let mut headers = http::HeaderMap::new();
+    /// headers.insert(
+    ///   "traceparent",
+    ///   "00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01".parse().unwrap()
+    /// );
+    /// headers.insert("tracestate", "rojo=00f067aa0ba902b7".parse().unwrap());
+    /// headers.append("tracestate", "congo=t61rcWkgMzE".parse().unwrap());
+    ///
+    /// let context = trace_context::TraceContext::extract(&headers).unwrap();
+    ///
+    /// assert_eq!(context.trace_state().to_string(), "rojo=00f067aa0ba902b7,congo=t61rcWkgMzE");
+    /// ```
+    pub fn trace_state(&self) -> &TraceState {
+        &self.trace_state
+    }
+
+    /// Return the vendor-specific trace data of the TraceContext for modification.
+    ///
+    /// ## Examples
+    /// ```
+    /// let mut context = trace_context::TraceContext::new_root();
+    /// context.trace_state_mut().insert("acme", "00f067aa0ba902b7").unwrap();
+    ///
+    /// let mut headers = http::HeaderMap::new();
+    /// context.inject(&mut headers);
+    ///
+    /// assert_eq!(headers.get("tracestate").unwrap(), "acme=00f067aa0ba902b7");
+    /// ```
+    pub fn trace_state_mut(&mut self) -> &mut TraceState {
+        &mut self.trace_state
+    }
+
     /// Returns true if the trace is sampled
     ///
     /// ## Examples
@@ -172,11 +840,13 @@ impl TraceContext {
     /// assert_eq!(context.sampled(), true);
     /// ```
     pub fn sampled(&self) -> bool {
-        (self.flags & 0b00000001) == 1
+        self.flags.contains(TraceFlags::SAMPLED)
     }
 
     /// Change sampled flag
     ///
+    /// Debug traces are always sampled, so clearing it also clears the debug flag.
+    ///
     /// ## Examples
     /// ```
     /// let mut context = trace_context::TraceContext::new_root();
@@ -185,34 +855,202 @@ impl TraceContext {
     /// assert_eq!(context.sampled(), false);
     /// ```
     pub fn set_sampled(&mut self, sampled: bool) {
-        let x = sampled as u8;
-        self.flags ^= (x ^ self.flags) & (1 << 0);
+        self.flags.set(TraceFlags::SAMPLED, sampled);
+        self.deferred = None;
+        if !sampled {
+            self.debug = false;
+        }
+    }
+
+    /// Returns true if the inbound header left the sampling decision to the receiver, as B3
+    /// and X-Ray headers can.
+    ///
+    /// Such a TraceContext isn't sampled, and propagators supporting it write back no decision
+    /// either. Changing the sampled flag, for example with a
+    /// [`Sampler`](trait.Sampler.html), makes the decision.
+    ///
+    /// ## Examples
+    /// ```
+    /// use trace_context::{B3Encoding, B3Propagator};
+    ///
+    /// let mut headers = http::HeaderMap::new();
+    /// headers.insert("b3", "00f067aa0ba902b7-b7ad6b7169203331".parse().unwrap());
+    ///
+    /// let mut context = B3Propagator::default().extract(&headers).unwrap().unwrap();
+    /// assert!(context.sampling_deferred());
+    ///
+    /// context.set_sampled(false);
+    /// assert!(!context.sampling_deferred());
+    /// ```
+    pub fn sampling_deferred(&self) -> bool {
+        self.deferred.is_some()
+    }
+
+    /// Clear the sampled flag and record that the decision is left to the receiver.
+    pub(crate) fn defer_sampling(&mut self, deferral: Deferral) {
+        self.set_sampled(false);
+        self.deferred = Some(deferral);
+    }
+
+    /// Return the number of traces this one represents when extrapolating from sampled traces,
+    /// as recorded by [`ConsistentProbability`](struct.ConsistentProbability.html) in the
+    /// `ot` tracestate entry.
+    ///
+    /// Unsampled traces count as zero. Returns `None` if the trace is sampled but the `ot`
+    /// entry has no valid threshold.
+    pub fn adjusted_count(&self) -> Option<f64> {
+        consistent::adjusted_count(self)
+    }
+
+    /// Returns true if the trace was marked for debugging by a propagation format that supports
+    /// it, such as B3.
+    ///
+    /// The W3C `traceparent` header has no debug flag, so it's not written by `inject`.
+    pub fn debug(&self) -> bool {
+        self.debug
+    }
+
+    /// Change debug flag
+    ///
+    /// Debug traces are always sampled, so enabling it also sets the sampled flag.
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+        if debug {
+            self.set_sampled(true);
+        }
+    }
+
+    /// Return the vendor-specific data of the inbound headers, such as the X-Ray `Lineage` field
+    /// or the Datadog origin.
+    ///
+    /// It's passed on to children and written back by the propagator that extracted it.
+    pub fn vendor_fields(&self) -> &VendorFields {
+        &self.vendor
+    }
+}
+
+/// The fields shared by the `traceparent` and `traceresponse` headers.
+pub(crate) struct HeaderFields {
+    pub(crate) version: u8,
+    pub(crate) trace_id: TraceId,
+    pub(crate) span_id: SpanId,
+    pub(crate) flags: TraceFlags,
+    pub(crate) extra_fields: bool,
+}
+
+impl HeaderFields {
+    /// Parse a `version-trace-id-span-id-trace-flags` header, where `span_name` is the name of
+    /// the span-id field in the given header.
+    pub(crate) fn parse(
+        header: &[u8],
+        name: &'static str,
+        span_name: &'static str,
+    ) -> Result<Self, ParseError> {
+        if !header.is_ascii() {
+            return Err(ParseError::NonAscii);
+        }
+
+        let mut parts = header.split(|&b| b == b'-');
+
+        let version = hex_field(parts.next(), "version", 2)? as u8;
+        if version == 0xff {
+            return Err(ParseError::InvalidVersion);
+        }
+
+        let trace_id = TraceId::from(hex_field(parts.next(), "trace-id", 32)?);
+        if !trace_id.is_valid() {
+            return Err(ParseError::ZeroTraceId);
+        }
+
+        let span_id = SpanId::from(hex_field(parts.next(), span_name, 16)? as u64);
+        if !span_id.is_valid() {
+            return Err(ParseError::ZeroParentId);
+        }
+
+        let flags = TraceFlags::from_bits(hex_field(parts.next(), "trace-flags", 2)? as u8);
+
+        let extra_fields = parts.next().is_some();
+        if extra_fields && version == SUPPORTED_VERSION {
+            return Err(ParseError::BadLength(name));
+        }
+
+        Ok(Self {
+            version,
+            trace_id,
+            span_id,
+            flags,
+            extra_fields,
+        })
+    }
+}
+
+/// Validate that a `traceparent` field is present, has the expected length and is lowercase hex,
+/// and return its value.
+pub(crate) fn hex_field(
+    part: Option<&[u8]>,
+    name: &'static str,
+    len: usize,
+) -> Result<u128, ParseError> {
+    let part = part.ok_or(ParseError::MissingField(name))?;
+    if part.len() != len {
+        return Err(ParseError::BadLength(name));
+    }
+    part.iter().try_fold(0, |value, &b| {
+        let digit = match b {
+            b'0'..=b'9' => b - b'0',
+            b'a'..=b'f' => b - b'a' + 10,
+            _ => return Err(ParseError::NotLowercaseHex(name)),
+        };
+        Ok(value << 4 | u128::from(digit))
+    })
+}
+
+/// Parse an unsigned 64-bit decimal field, as used by propagation formats like Datadog.
+pub(crate) fn decimal_field(value: &str, name: &'static str) -> Result<u64, ParseError> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(ParseError::NotDecimal(name));
+    }
+    value.parse().map_err(|_| ParseError::NotDecimal(name))
+}
+
+/// Write `value` as lowercase hex, zero-padded to fill `buf`.
+fn write_hex(buf: &mut [u8], mut value: u128) {
+    for b in buf.iter_mut().rev() {
+        *b = b"0123456789abcdef"[(value & 0xf) as usize];
+        value >>= 4;
     }
 }
 
 impl fmt::Display for TraceContext {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{:02x}-{:032}-{:016x}-{:02x}",
-            self.version, self.trace_id, self.id, self.flags
-        )
+        f.write_str(std::str::from_utf8(&self.to_bytes()).unwrap())
     }
 }
 
 #[cfg(test)]
 mod test {
     mod extract {
+        use crate::ParseError;
+
+        fn extract(traceparent: &str) -> Result<crate::TraceContext, ParseError> {
+            let mut headers = http::HeaderMap::new();
+            headers.insert("traceparent", traceparent.parse().unwrap());
+            crate::TraceContext::extract(&headers)
+        }
+
         #[test]
         fn default() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
             let mut headers = http::HeaderMap::new();
-            headers.insert("traceparent", "00-01-deadbeef-00".parse()?);
+            headers.insert(
+                "traceparent",
+                "00-00000000000000000000000000000001-00000000deadbeef-00".parse()?,
+            );
             let context = crate::TraceContext::extract(&headers)?;
             assert_eq!(context.version(), 0);
-            assert_eq!(context.trace_id(), 1);
-            assert_eq!(context.parent_id().unwrap(), 3735928559);
-            assert_eq!(context.flags(), 0);
-            assert_eq!(context.sampled(), false);
+            assert_eq!(context.trace_id(), crate::TraceId::from(1));
+            assert_eq!(context.parent_id(), Some(crate::SpanId::from(3735928559)));
+            assert_eq!(context.flags(), crate::TraceFlags::default());
+            assert!(!context.sampled());
             Ok(())
         }
 
@@ -222,26 +1060,430 @@ mod test {
             let context = crate::TraceContext::extract(&headers)?;
             assert_eq!(context.version(), 0);
             assert_eq!(context.parent_id(), None);
-            assert_eq!(context.flags(), 1);
-            assert_eq!(context.sampled(), true);
+            assert_eq!(context.flags(), crate::TraceFlags::SAMPLED);
+            assert!(context.sampled());
             Ok(())
         }
 
         #[test]
         fn not_sampled() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let context = extract("00-00000000000000000000000000000001-0000000000000002-00")?;
+            assert!(!context.sampled());
+            Ok(())
+        }
+
+        #[test]
+        fn sampled() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let context = extract("00-00000000000000000000000000000001-0000000000000002-01")?;
+            assert!(context.sampled());
+            Ok(())
+        }
+
+        #[test]
+        fn missing_field() {
+            assert_eq!(
+                extract("00-01").unwrap_err(),
+                ParseError::BadLength("trace-id")
+            );
+            assert_eq!(
+                extract("00-00000000000000000000000000000001").unwrap_err(),
+                ParseError::MissingField("parent-id")
+            );
+            assert_eq!(
+                extract("00-00000000000000000000000000000001-0000000000000002").unwrap_err(),
+                ParseError::MissingField("trace-flags")
+            );
+        }
+
+        #[test]
+        fn bad_length() {
+            assert_eq!(
+                extract("00-00000000000000000000000000000001-02-01").unwrap_err(),
+                ParseError::BadLength("parent-id")
+            );
+            assert_eq!(
+                extract("00-00000000000000000000000000000001-0000000000000002-01-").unwrap_err(),
+                ParseError::BadLength("traceparent")
+            );
+        }
+
+        #[test]
+        fn not_lowercase_hex() {
+            assert_eq!(
+                extract("00-0AF7651916CD43DD8448EB211C80319C-0000000000000002-01").unwrap_err(),
+                ParseError::NotLowercaseHex("trace-id")
+            );
+            assert_eq!(
+                extract("00-00000000000000000000000000000001-000000000000000g-01").unwrap_err(),
+                ParseError::NotLowercaseHex("parent-id")
+            );
+        }
+
+        #[test]
+        fn zero_ids() {
+            assert_eq!(
+                extract("00-00000000000000000000000000000000-0000000000000002-01").unwrap_err(),
+                ParseError::ZeroTraceId
+            );
+            assert_eq!(
+                extract("00-00000000000000000000000000000001-0000000000000000-01").unwrap_err(),
+                ParseError::ZeroParentId
+            );
+        }
+
+        #[test]
+        fn invalid_version() {
+            assert_eq!(
+                extract("ff-00000000000000000000000000000001-0000000000000002-01").unwrap_err(),
+                ParseError::InvalidVersion
+            );
+        }
+
+        #[test]
+        fn non_ascii() {
             let mut headers = http::HeaderMap::new();
-            headers.insert("traceparent", "00-01-02-00".parse().unwrap());
+            headers.insert(
+                "traceparent",
+                http::header::HeaderValue::from_bytes(b"00-\xe2\x98\x83-01").unwrap(),
+            );
+            assert_eq!(
+                crate::TraceContext::extract(&headers).unwrap_err(),
+                ParseError::NonAscii
+            );
+        }
+    }
+
+    mod trace_state {
+        fn headers(trace_state: &[&str]) -> http::HeaderMap {
+            let mut headers = http::HeaderMap::new();
+            headers.insert(
+                "traceparent",
+                "00-00000000000000000000000000000001-0000000000000002-01"
+                    .parse()
+                    .unwrap(),
+            );
+            for value in trace_state {
+                headers.append("tracestate", value.parse().unwrap());
+            }
+            headers
+        }
+
+        #[test]
+        fn combined() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let headers = headers(&["acme=1,dd=s:1", "fw529a3039@dt=d4cd"]);
             let context = crate::TraceContext::extract(&headers)?;
-            assert_eq!(context.sampled(), false);
+            assert_eq!(
+                context.trace_state().to_string(),
+                "acme=1,dd=s:1,fw529a3039@dt=d4cd"
+            );
             Ok(())
         }
 
         #[test]
-        fn sampled() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        fn invalid_is_discarded() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>>
+        {
+            let headers = headers(&["acme=1", "acme=2"]);
+            let context = crate::TraceContext::extract(&headers)?;
+            assert!(context.trace_state().is_empty());
+            Ok(())
+        }
+
+        #[test]
+        fn non_ascii_is_discarded() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>>
+        {
+            let mut headers = headers(&["a=1"]);
+            headers.append("tracestate", http::HeaderValue::from_bytes(b"b=\xe9")?);
+            let context = crate::TraceContext::extract(&headers)?;
+            assert!(context.trace_state().is_empty());
+            Ok(())
+        }
+
+        #[test]
+        fn without_traceparent() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
             let mut headers = http::HeaderMap::new();
-            headers.insert("traceparent", "00-01-02-01".parse().unwrap());
+            headers.insert("tracestate", "acme=1".parse()?);
             let context = crate::TraceContext::extract(&headers)?;
-            assert_eq!(context.sampled(), true);
+            assert!(context.trace_state().is_empty());
+            Ok(())
+        }
+
+        #[test]
+        fn inject() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let context = crate::TraceContext::extract(&headers(&["acme=1", "dd=s:1"]))?;
+            let mut output = http::HeaderMap::new();
+            context.child().inject(&mut output);
+            assert_eq!(output.get("tracestate").unwrap(), "acme=1,dd=s:1");
+
+            let mut output = headers(&["stale=1"]);
+            crate::TraceContext::new_root().inject(&mut output);
+            assert!(output.get("tracestate").is_none());
+            Ok(())
+        }
+    }
+
+    mod child {
+        use crate::{IdGenerator, SeededIdGenerator, TraceFlags, UnknownFlags};
+
+        #[test]
+        fn with_generator() {
+            let parent = crate::TraceContext::new_root();
+            let child = parent.child_with(&SeededIdGenerator::new(3));
+            assert_eq!(child.id(), SeededIdGenerator::new(3).new_span_id());
+            assert_eq!(child.trace_id(), parent.trace_id());
+            assert_eq!(child.parent_id(), Some(parent.id()));
+        }
+
+        #[test]
+        fn clears_unknown_flags() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>>
+        {
+            let mut headers = http::HeaderMap::new();
+            headers.insert(
+                "traceparent",
+                "00-00000000000000000000000000000001-0000000000000002-83".parse()?,
+            );
+            let parent = crate::TraceContext::extract(&headers)?;
+            assert_eq!(parent.flags().bits(), 0x83);
+
+            let mut child = parent.child();
+            assert_eq!(child.flags(), TraceFlags::SAMPLED | TraceFlags::RANDOM);
+
+            child.set_flags(parent.flags().for_child(UnknownFlags::Propagate));
+            assert_eq!(child.flags().bits(), 0x83);
+            Ok(())
+        }
+    }
+
+    mod future_version {
+        use crate::ParseError;
+
+        fn extract(traceparent: &str) -> Result<crate::TraceContext, ParseError> {
+            let mut headers = http::HeaderMap::new();
+            headers.insert("traceparent", traceparent.parse().unwrap());
+            crate::TraceContext::extract(&headers)
+        }
+
+        #[test]
+        fn without_extra_fields() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>>
+        {
+            let context = extract("01-00000000000000000000000000000001-0000000000000002-01")?;
+            assert_eq!(context.version(), 1);
+            assert_eq!(context.trace_id(), crate::TraceId::from(1));
+            assert_eq!(context.parent_id(), Some(crate::SpanId::from(2)));
+            assert!(context.sampled());
+            assert!(!context.has_extra_fields());
+            Ok(())
+        }
+
+        #[test]
+        fn with_extra_fields() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let context = extract("01-00000000000000000000000000000001-0000000000000002-01-ab-cd")?;
+            assert_eq!(context.version(), 1);
+            assert_eq!(context.trace_id(), crate::TraceId::from(1));
+            assert!(context.has_extra_fields());
+            Ok(())
+        }
+
+        #[test]
+        fn flags_not_followed_by_dash() {
+            assert_eq!(
+                extract("01-00000000000000000000000000000001-0000000000000002-01ab").unwrap_err(),
+                ParseError::BadLength("trace-flags")
+            );
+        }
+
+        #[test]
+        fn inject_downgrades() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let context = extract("01-00000000000000000000000000000001-0000000000000002-01-ab")?;
+            let mut headers = http::HeaderMap::new();
+            context.inject(&mut headers);
+            let traceparent = headers.get("traceparent").unwrap().to_str()?;
+            assert!(traceparent.starts_with("00-00000000000000000000000000000001-"));
+            assert_eq!(traceparent.len(), 55);
+            assert_eq!(context.child().version(), 0);
+            Ok(())
+        }
+    }
+
+    mod bytes {
+        use crate::ParseError;
+
+        const TRACEPARENT: &[u8] = b"00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01";
+
+        #[test]
+        fn from_bytes() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let context = crate::TraceContext::from_bytes(TRACEPARENT)?;
+            assert_eq!(
+                context.trace_id(),
+                "0af7651916cd43dd8448eb211c80319c".parse()?
+            );
+            assert_eq!(context.parent_id(), Some("00f067aa0ba902b7".parse()?));
+            assert!(context.sampled());
+            Ok(())
+        }
+
+        #[test]
+        fn non_ascii() {
+            assert_eq!(
+                crate::TraceContext::from_bytes(b"00-\xff").unwrap_err(),
+                ParseError::NonAscii
+            );
+        }
+
+        #[test]
+        fn to_bytes() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let context = crate::TraceContext::from_bytes(TRACEPARENT)?;
+            let child = context.child();
+            let bytes = child.to_bytes();
+            assert_eq!(&bytes[..36], &TRACEPARENT[..36]);
+            assert_eq!(&bytes[52..], b"-01");
+            assert_eq!(&bytes[..], child.to_string().as_bytes());
+            assert_eq!(
+                crate::TraceContext::from_bytes(&bytes)?.parent_id(),
+                Some(child.id())
+            );
+            Ok(())
+        }
+    }
+
+    mod binary {
+        use crate::{ParseError, TraceContext};
+
+        const BINARY: &[u8] = &[
+            0, 0, 0x4b, 0xf9, 0x2f, 0x35, 0x77, 0xb3, 0x4d, 0xa6, 0xa3, 0xce, 0x92, 0x9d, 0x0e,
+            0x0e, 0x47, 0x36, 1, 0x00, 0xf0, 0x67, 0xaa, 0x0b, 0xa9, 0x02, 0xb7, 2, 1,
+        ];
+
+        #[test]
+        fn from_binary() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let context = TraceContext::from_binary(BINARY)?;
+            assert_eq!(
+                context.trace_id(),
+                "4bf92f3577b34da6a3ce929d0e0e4736".parse()?
+            );
+            assert_eq!(context.parent_id(), Some("00f067aa0ba902b7".parse()?));
+            assert!(context.sampled());
+            Ok(())
+        }
+
+        #[test]
+        fn to_binary() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let context = TraceContext::from_binary(BINARY)?;
+            let bytes = context.to_binary();
+            assert_eq!(&bytes[..19], &BINARY[..19]);
+            assert_eq!(&bytes[19..27], &context.id().to_bytes());
+            assert_eq!(&bytes[27..], &BINARY[27..]);
+            Ok(())
+        }
+
+        #[test]
+        fn unknown_fields() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let mut bytes = BINARY.to_vec();
+            bytes[0] = 1;
+            bytes.extend_from_slice(&[3, 0xde, 0xad]);
+            let context = TraceContext::from_binary(&bytes)?;
+            assert_eq!(context.parent_id(), Some("00f067aa0ba902b7".parse()?));
+
+            let context = TraceContext::from_binary(&BINARY[..27])?;
+            assert!(!context.sampled());
+            Ok(())
+        }
+
+        #[test]
+        fn invalid() {
+            assert_eq!(
+                TraceContext::from_binary(&[]).unwrap_err(),
+                ParseError::MissingField("version")
+            );
+            assert_eq!(
+                TraceContext::from_binary(&BINARY[..10]).unwrap_err(),
+                ParseError::BadLength("trace-id")
+            );
+            assert_eq!(
+                TraceContext::from_binary(&BINARY[..18]).unwrap_err(),
+                ParseError::MissingField("span-id")
+            );
+            let mut bytes = BINARY.to_vec();
+            bytes[2..18].copy_from_slice(&[0; 16]);
+            assert_eq!(
+                TraceContext::from_binary(&bytes).unwrap_err(),
+                ParseError::ZeroTraceId
+            );
+        }
+    }
+
+    mod carrier {
+        use std::collections::HashMap;
+
+        #[test]
+        fn round_trip() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let mut context = crate::TraceContext::new_root();
+            context.trace_state_mut().insert("acme", "1")?;
+
+            let mut carrier: HashMap<String, String> = HashMap::new();
+            context.inject_into(&mut carrier);
+            let child = crate::TraceContext::extract_from(&carrier)?;
+            assert_eq!(child.trace_id(), context.trace_id());
+            assert_eq!(child.parent_id(), Some(context.id()));
+            assert_eq!(child.trace_state(), context.trace_state());
+            Ok(())
+        }
+
+        #[test]
+        fn header_map() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let context = crate::TraceContext::new_root();
+            let mut headers = http::HeaderMap::new();
+            context.inject_into(&mut headers);
+            let child = crate::TraceContext::extract_from(&headers)?;
+            assert_eq!(child.parent_id(), Some(context.id()));
+            Ok(())
+        }
+
+        #[test]
+        fn missing() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let carrier: HashMap<String, String> = HashMap::new();
+            let context = crate::TraceContext::extract_from(&carrier)?;
+            assert_eq!(context.parent_id(), None);
+            Ok(())
+        }
+
+        #[test]
+        fn invalid() {
+            let mut carrier = HashMap::new();
+            carrier.insert("traceparent".to_owned(), "00-01".to_owned());
+            assert!(crate::TraceContext::extract_from(&carrier).is_err());
+        }
+
+        #[test]
+        fn non_ascii() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            use crate::{ParseError, Propagator, TraceContextPropagator};
+
+            let mut headers = http::HeaderMap::new();
+            headers.insert("traceparent", http::HeaderValue::from_bytes(b"00-\xe9-01")?);
+            assert_eq!(
+                crate::TraceContext::extract(&headers).unwrap_err(),
+                ParseError::NonAscii
+            );
+            assert_eq!(
+                crate::TraceContext::extract_from(&headers).unwrap_err(),
+                ParseError::NonAscii
+            );
+            assert_eq!(
+                TraceContextPropagator::new().extract(&headers).unwrap_err(),
+                ParseError::NonAscii
+            );
+            Ok(())
+        }
+    }
+
+    mod inject {
+        #[test]
+        fn round_trip() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+            let context = crate::TraceContext::new_root();
+            let mut headers = http::HeaderMap::new();
+            context.inject(&mut headers);
+            let child = crate::TraceContext::extract(&headers)?;
+            assert_eq!(child.trace_id(), context.trace_id());
+            assert_eq!(child.parent_id(), Some(context.id()));
             Ok(())
         }
     }
@@ -0,0 +1,236 @@
+use crate::trace_state::MAX_MEMBERS;
+use crate::ParseError;
+
+/// Limits enforced by
+/// [`TraceContext::extract_with_limits`](struct.TraceContext.html#method.extract_with_limits)
+/// before any header is parsed, to bound the work done for abusive requests. Call
+/// [`check`](#method.check) directly to combine them with other extraction methods.
+///
+/// By default each of the `traceparent` and the combined `tracestate` headers is limited to
+/// 4096 bytes, `tracestate` to the 32 list-members allowed by the spec, and multiple
+/// `traceparent` headers are rejected, as the spec considers them invalid.
+///
+/// ## Examples
+/// ```
+/// use trace_context::{ExtractLimits, ParseError, TraceContext};
+///
+/// let mut headers = http::HeaderMap::new();
+/// headers.insert(
+///     "traceparent",
+///     "00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01".parse().unwrap(),
+/// );
+/// headers.insert("tracestate", "a=1,b=2,c=3".parse().unwrap());
+///
+/// let limits = ExtractLimits::new().max_trace_state_members(2);
+///
+/// assert_eq!(
+///     TraceContext::extract_with_limits(&headers, &limits).unwrap_err(),
+///     ParseError::TooManyTraceStateMembers,
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractLimits {
+    max_header_len: usize,
+    max_trace_state_members: usize,
+    reject_duplicate_traceparent: bool,
+}
+
+impl ExtractLimits {
+    /// Create ExtractLimits with the default limits.
+    pub fn new() -> Self {
+        Self {
+            max_header_len: 4096,
+            max_trace_state_members: MAX_MEMBERS,
+            reject_duplicate_traceparent: true,
+        }
+    }
+
+    /// Set the maximum length in bytes of the `traceparent` header, and of all `tracestate`
+    /// headers combined.
+    pub fn max_header_len(mut self, max_header_len: usize) -> Self {
+        self.max_header_len = max_header_len;
+        self
+    }
+
+    /// Set the maximum number of `tracestate` list-members. Values above 32 are capped at 32.
+    pub fn max_trace_state_members(mut self, max_members: usize) -> Self {
+        self.max_trace_state_members = max_members.min(MAX_MEMBERS);
+        self
+    }
+
+    /// Set whether multiple `traceparent` headers are rejected. If not, the first one is used.
+    pub fn reject_duplicate_traceparent(mut self, reject: bool) -> Self {
+        self.reject_duplicate_traceparent = reject;
+        self
+    }
+
+    /// Check the headers against the limits without parsing them.
+    ///
+    /// The `tracestate` limits are only enforced if there is a `traceparent` header, since
+    /// `tracestate` is ignored otherwise.
+    ///
+    /// ## Examples
+    /// ```
+    /// use trace_context::{AlwaysOff, ExtractLimits, IngressPolicy, TraceContext};
+    ///
+    /// let mut headers = http::HeaderMap::new();
+    /// headers.insert(
+    ///     "traceparent",
+    ///     "00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01".parse().unwrap(),
+    /// );
+    ///
+    /// ExtractLimits::new().check(&headers).unwrap();
+    /// let (context, _) =
+    ///     TraceContext::extract_with_policy(&headers, IngressPolicy::RestartTrace, &AlwaysOff)
+    ///         .unwrap();
+    ///
+    /// assert_eq!(context.parent_id(), None);
+    /// ```
+    pub fn check(&self, headers: &http::HeaderMap) -> Result<(), ParseError> {
+        let mut traceparents = headers.get_all("traceparent").iter();
+        match traceparents.next() {
+            Some(traceparent) => {
+                if self.reject_duplicate_traceparent && traceparents.next().is_some() {
+                    return Err(ParseError::DuplicateTraceparent);
+                }
+                if traceparent.len() > self.max_header_len {
+                    return Err(ParseError::HeaderTooLong("traceparent"));
+                }
+            }
+            None => return Ok(()),
+        }
+
+        let trace_state = headers.get_all("tracestate");
+        let mut len = 0;
+        for value in trace_state.iter() {
+            len += value.len() + 1;
+            if len - 1 > self.max_header_len {
+                return Err(ParseError::HeaderTooLong("tracestate"));
+            }
+        }
+
+        let members = trace_state
+            .iter()
+            .flat_map(|value| value.as_bytes().split(|&b| b == b','))
+            .filter(|member| !member.iter().all(|&b| b == b' ' || b == b'\t'))
+            .count();
+        if members > self.max_trace_state_members {
+            return Err(ParseError::TooManyTraceStateMembers);
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ExtractLimits;
+    use crate::{ParseError, TraceContext};
+
+    const TRACEPARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01";
+
+    #[test]
+    fn within_limits() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("traceparent", TRACEPARENT.parse()?);
+        headers.append("tracestate", "a=1, b=2".parse()?);
+        headers.append("tracestate", "c=3".parse()?);
+        let context = TraceContext::extract_with_limits(&headers, &ExtractLimits::new())?;
+        assert_eq!(context.parent_id(), Some("00f067aa0ba902b7".parse()?));
+        assert_eq!(context.trace_state().len(), 3);
+
+        let context =
+            TraceContext::extract_with_limits(&http::HeaderMap::new(), &ExtractLimits::new())?;
+        assert_eq!(context.parent_id(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_traceparent() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut headers = http::HeaderMap::new();
+        for _ in 0..100 {
+            headers.append("traceparent", TRACEPARENT.parse()?);
+        }
+        assert_eq!(
+            TraceContext::extract_with_limits(&headers, &ExtractLimits::new()).unwrap_err(),
+            ParseError::DuplicateTraceparent
+        );
+
+        let limits = ExtractLimits::new().reject_duplicate_traceparent(false);
+        let context = TraceContext::extract_with_limits(&headers, &limits)?;
+        assert_eq!(context.parent_id(), Some("00f067aa0ba902b7".parse()?));
+        Ok(())
+    }
+
+    #[test]
+    fn header_too_long() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut headers = http::HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            format!("{}-{}", TRACEPARENT, "0".repeat(64)).parse()?,
+        );
+        let limits = ExtractLimits::new().max_header_len(64);
+        assert_eq!(
+            TraceContext::extract_with_limits(&headers, &limits).unwrap_err(),
+            ParseError::HeaderTooLong("traceparent")
+        );
+
+        headers.insert("traceparent", TRACEPARENT.parse()?);
+        headers.insert("tracestate", format!("a={}", "x".repeat(5000)).parse()?);
+        assert_eq!(
+            TraceContext::extract_with_limits(&headers, &ExtractLimits::new()).unwrap_err(),
+            ParseError::HeaderTooLong("tracestate")
+        );
+
+        // The combined length of multiple headers counts the separating comma.
+        headers.insert("tracestate", format!("a={}", "x".repeat(60)).parse()?);
+        headers.append("tracestate", "b=2".parse()?);
+        assert!(TraceContext::extract_with_limits(
+            &headers,
+            &ExtractLimits::new().max_header_len(66)
+        )
+        .is_ok());
+        assert_eq!(
+            TraceContext::extract_with_limits(&headers, &ExtractLimits::new().max_header_len(65))
+                .unwrap_err(),
+            ParseError::HeaderTooLong("tracestate")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn too_many_members() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let members: Vec<String> = (0..33).map(|i| format!("k{}=v", i)).collect();
+        let mut headers = http::HeaderMap::new();
+        headers.insert("traceparent", TRACEPARENT.parse()?);
+        headers.insert("tracestate", members.join(",").parse()?);
+        assert_eq!(
+            TraceContext::extract_with_limits(&headers, &ExtractLimits::new()).unwrap_err(),
+            ParseError::TooManyTraceStateMembers
+        );
+
+        headers.insert("tracestate", "a=1,,b=2, ".parse()?);
+        let limits = ExtractLimits::new().max_trace_state_members(2);
+        assert!(TraceContext::extract_with_limits(&headers, &limits).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn trace_state_without_traceparent(
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let members: Vec<String> = (0..33).map(|i| format!("k{}=v", i)).collect();
+        let mut headers = http::HeaderMap::new();
+        headers.insert("tracestate", members.join(",").parse()?);
+        headers.append("tracestate", format!("a={}", "x".repeat(5000)).parse()?);
+        let context = TraceContext::extract_with_limits(&headers, &ExtractLimits::new())?;
+        assert_eq!(context.parent_id(), None);
+        assert!(context.trace_state().is_empty());
+        Ok(())
+    }
+}
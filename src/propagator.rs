@@ -0,0 +1,94 @@
+use crate::{Extractor, IdGenerator, Injector, ParseError, RandomIdGenerator, TraceContext};
+
+/// A propagation format that can be extracted from and injected into a carrier.
+///
+/// Implemented by every propagator of this crate, so they can be combined with
+/// [`CompositePropagator`](struct.CompositePropagator.html).
+pub trait Propagator {
+    /// The name of the format, such as `tracecontext` or `b3`.
+    fn name(&self) -> &'static str;
+
+    /// Create and return a TraceContext based on the carrier, or `Ok(None)` if the carrier has
+    /// no headers of this format.
+    fn extract(&self, carrier: &dyn Extractor) -> Result<Option<TraceContext>, ParseError> {
+        self.extract_with(carrier, &RandomIdGenerator::new())
+    }
+
+    /// Create and return a TraceContext like [`extract`](#method.extract), using `gen` to
+    /// generate its id.
+    fn extract_with(
+        &self,
+        carrier: &dyn Extractor,
+        gen: &dyn IdGenerator,
+    ) -> Result<Option<TraceContext>, ParseError>;
+
+    /// Add the headers of this format for the TraceContext to the carrier.
+    fn inject(&self, context: &TraceContext, carrier: &mut dyn Injector);
+}
+
+/// Converts between the W3C `traceparent` and `tracestate` headers and TraceContext.
+///
+/// Unlike [`TraceContext::extract_from`](struct.TraceContext.html#method.extract_from), a
+/// missing `traceparent` header results in `Ok(None)` rather than a new root.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TraceContextPropagator;
+
+impl TraceContextPropagator {
+    /// Create a TraceContextPropagator.
+    pub fn new() -> Self {
+        TraceContextPropagator
+    }
+}
+
+impl Propagator for TraceContextPropagator {
+    fn name(&self) -> &'static str {
+        "tracecontext"
+    }
+
+    fn extract_with(
+        &self,
+        carrier: &dyn Extractor,
+        gen: &dyn IdGenerator,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        if !carrier.contains_key("traceparent") {
+            return Ok(None);
+        }
+        TraceContext::extract_from_with(carrier, gen).map(Some)
+    }
+
+    fn inject(&self, context: &TraceContext, carrier: &mut dyn Injector) {
+        context.inject_into(carrier);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Propagator, TraceContextPropagator};
+    use crate::carrier::test::carrier;
+    use crate::{
+        B3Propagator, CompositePropagator, DatadogPropagator, GcpPropagator, JaegerPropagator,
+        XRayPropagator,
+    };
+
+    #[test]
+    fn missing() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let propagators: Vec<Box<dyn Propagator>> = vec![
+            Box::new(TraceContextPropagator::new()),
+            Box::new(B3Propagator::default()),
+            Box::new(JaegerPropagator::new()),
+            Box::new(XRayPropagator::new()),
+            Box::new(DatadogPropagator::new()),
+            Box::new(GcpPropagator::new()),
+            Box::new(CompositePropagator::new().with(B3Propagator::default())),
+        ];
+        let carrier = carrier(&[("baggage", "acme=1")]);
+        for propagator in &propagators {
+            assert!(
+                propagator.extract(&carrier)?.is_none(),
+                "{} extracted a TraceContext",
+                propagator.name()
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+use crate::{Sampler, SamplingDecision, TraceContext, TraceId};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// A source of the current time, so time-dependent samplers can be tested deterministically.
+pub trait Clock {
+    /// Return the current time.
+    fn now(&self) -> Instant;
+}
+
+/// The system's monotonic clock.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Samples at most a given number of traces per second, using a token bucket.
+///
+/// The bucket starts full and holds up to `burst` tokens, one of which is spent per sampled
+/// trace. If the rate is zero or the burst is below one trace, it starts empty instead, so no
+/// trace is sampled before the rate allows it. It's safe to share between threads. Wrap it in
+/// [`ParentBased`](struct.ParentBased.html) to only limit roots.
+///
+/// ## Examples
+/// ```
+/// use trace_context::{RateLimiting, TraceContext};
+///
+/// let sampler = RateLimiting::new(100.0).burst(2.0);
+///
+/// assert!(TraceContext::new_root_with_sampler(&sampler).sampled());
+/// assert!(TraceContext::new_root_with_sampler(&sampler).sampled());
+/// assert!(!TraceContext::new_root_with_sampler(&sampler).sampled());
+/// ```
+#[derive(Debug)]
+pub struct RateLimiting<C = SystemClock> {
+    per_second: f64,
+    burst: f64,
+    clock: C,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiting {
+    /// Create a RateLimiting sampler allowing `per_second` traces per second, with a burst of
+    /// one second's worth of traces.
+    pub fn new(per_second: f64) -> Self {
+        Self::with_clock(per_second, SystemClock)
+    }
+}
+
+impl<C: Clock> RateLimiting<C> {
+    /// Create a RateLimiting sampler reading the time from `clock`.
+    pub fn with_clock(per_second: f64, clock: C) -> Self {
+        let per_second = per_second.max(0.0);
+        let bucket = Mutex::new(Bucket {
+            tokens: 0.0,
+            updated: clock.now(),
+        });
+        Self {
+            per_second,
+            burst: 0.0,
+            clock,
+            bucket,
+        }
+        .burst(per_second)
+    }
+
+    /// Set the number of traces that can be sampled at once after a quiet period. Also refills
+    /// the bucket, unless the rate is zero or `burst` is below one.
+    pub fn burst(mut self, burst: f64) -> Self {
+        self.burst = burst.max(1.0);
+        self.bucket.get_mut().unwrap().tokens = if self.per_second > 0.0 && burst >= 1.0 {
+            self.burst
+        } else {
+            0.0
+        };
+        self
+    }
+
+    /// Return the number of traces sampled per second.
+    pub fn per_second(&self) -> f64 {
+        self.per_second
+    }
+}
+
+impl<C: Clock> Sampler for RateLimiting<C> {
+    fn should_sample(&self, _: TraceId, _: Option<&TraceContext>) -> SamplingDecision {
+        let now = self.clock.now();
+        let mut bucket = self.bucket.lock().unwrap();
+
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            SamplingDecision::RecordAndSample
+        } else {
+            SamplingDecision::Drop
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Clock, RateLimiting};
+    use crate::{ParentBased, Sampler, TraceContext, TraceId};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    #[derive(Clone)]
+    struct ManualClock(Arc<Mutex<Instant>>);
+
+    impl ManualClock {
+        fn new() -> Self {
+            ManualClock(Arc::new(Mutex::new(Instant::now())))
+        }
+
+        fn advance(&self, duration: Duration) {
+            *self.0.lock().unwrap() += duration;
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn sampled<S: Sampler>(sampler: &S, count: usize) -> usize {
+        (0..count)
+            .filter(|_| sampler.should_sample(TraceId::from(1), None).is_sampled())
+            .count()
+    }
+
+    #[test]
+    fn rate() {
+        let clock = ManualClock::new();
+        let sampler = RateLimiting::with_clock(10.0, clock.clone());
+        assert_eq!(sampled(&sampler, 100), 10);
+
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(sampled(&sampler, 100), 5);
+
+        clock.advance(Duration::from_millis(50));
+        assert_eq!(sampled(&sampler, 100), 0);
+        clock.advance(Duration::from_millis(50));
+        assert_eq!(sampled(&sampler, 100), 1);
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(sampled(&sampler, 100), 10);
+    }
+
+    #[test]
+    fn burst() {
+        let clock = ManualClock::new();
+        let sampler = RateLimiting::with_clock(2.0, clock.clone()).burst(5.0);
+        assert_eq!(sampled(&sampler, 100), 5);
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(sampled(&sampler, 100), 2);
+
+        let sampler = RateLimiting::with_clock(0.0, clock.clone());
+        assert_eq!(sampled(&sampler, 100), 0);
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(sampled(&sampler, 100), 0);
+
+        let sampler = RateLimiting::with_clock(0.0, clock.clone()).burst(5.0);
+        assert_eq!(sampled(&sampler, 100), 0);
+
+        let sampler = RateLimiting::with_clock(0.5, clock.clone());
+        assert_eq!(sampled(&sampler, 100), 0);
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(sampled(&sampler, 100), 1);
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(sampled(&sampler, 100), 1);
+    }
+
+    #[test]
+    fn parent_based() {
+        let sampler = ParentBased::new(RateLimiting::with_clock(1.0, ManualClock::new()));
+        let parent = TraceContext::new_root();
+        assert!(sampler.should_sample(parent.trace_id(), None).is_sampled());
+        assert!(!sampler.should_sample(parent.trace_id(), None).is_sampled());
+        assert!(sampler
+            .should_sample(parent.trace_id(), Some(&parent))
+            .is_sampled());
+    }
+
+    #[test]
+    fn threads() {
+        let clock = ManualClock::new();
+        let sampler = Arc::new(RateLimiting::with_clock(100.0, clock));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let sampler = sampler.clone();
+                std::thread::spawn(move || sampled(&*sampler, 100))
+            })
+            .collect();
+        let total: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(total, 100);
+    }
+}
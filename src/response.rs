@@ -0,0 +1,128 @@
+use crate::{HeaderFields, ParseError, SpanId, TraceFlags, TraceId};
+use std::fmt;
+use std::str::FromStr;
+
+/// The trace context a server reports in the `traceresponse` header of the
+/// [Trace Context Level 2](https://w3c.github.io/trace-context/#traceresponse-header) draft.
+///
+/// The trace id differs from the one the caller sent if the server restarted the trace.
+///
+/// ## Examples
+/// ```
+/// let response: trace_context::TraceResponse =
+///     "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00".parse().unwrap();
+///
+/// assert_eq!(response.trace_id().to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+/// assert_eq!(response.child_id().to_string(), "00f067aa0ba902b7");
+/// assert_eq!(response.sampled(), false);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceResponse {
+    version: u8,
+    trace_id: TraceId,
+    child_id: SpanId,
+    flags: TraceFlags,
+}
+
+impl TraceResponse {
+    /// Parse a `traceresponse` header value.
+    ///
+    /// As with `traceparent`, headers with a higher version than supported are parsed leniently.
+    pub fn from_bytes(traceresponse: &[u8]) -> Result<Self, ParseError> {
+        let fields = HeaderFields::parse(traceresponse, "traceresponse", "child-id")?;
+        Ok(Self {
+            version: fields.version,
+            trace_id: fields.trace_id,
+            child_id: fields.span_id,
+            flags: fields.flags,
+        })
+    }
+
+    /// Return the version of the header.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Return the trace id the server used.
+    pub fn trace_id(&self) -> TraceId {
+        self.trace_id
+    }
+
+    /// Return the id of the server span.
+    pub fn child_id(&self) -> SpanId {
+        self.child_id
+    }
+
+    /// Return the trace flags the server used.
+    pub fn flags(&self) -> TraceFlags {
+        self.flags
+    }
+
+    /// Returns true if the server sampled the request.
+    pub fn sampled(&self) -> bool {
+        self.flags.contains(TraceFlags::SAMPLED)
+    }
+}
+
+impl FromStr for TraceResponse {
+    type Err = ParseError;
+
+    fn from_str(traceresponse: &str) -> Result<Self, Self::Err> {
+        Self::from_bytes(traceresponse.as_bytes())
+    }
+}
+
+impl fmt::Display for TraceResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:02x}-{}-{}-{}",
+            self.version, self.trace_id, self.child_id, self.flags
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TraceResponse;
+    use crate::{ParseError, TraceContext};
+
+    #[test]
+    fn round_trip() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut context = TraceContext::new_root();
+        context.set_sampled(false);
+        let mut headers = http::HeaderMap::new();
+        context.inject_response(&mut headers);
+        assert_eq!(headers["traceresponse"], context.to_string().as_str());
+        assert!(!headers.contains_key("traceparent"));
+
+        let response = TraceContext::extract_response(&headers)?.unwrap();
+        assert_eq!(response.trace_id(), context.trace_id());
+        assert_eq!(response.child_id(), context.id());
+        assert!(!response.sampled());
+        assert_eq!(response.to_string(), context.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn missing() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        assert!(TraceContext::extract_response(&http::HeaderMap::new())?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736".parse::<TraceResponse>(),
+            Err(ParseError::MissingField("child-id"))
+        );
+        assert_eq!(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01".parse::<TraceResponse>(),
+            Err(ParseError::ZeroParentId)
+        );
+        assert_eq!(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-x".parse::<TraceResponse>(),
+            Err(ParseError::BadLength("traceresponse"))
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use crate::{TraceContext, TraceId};
+
+/// The outcome of a [`Sampler`](trait.Sampler.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplingDecision {
+    /// The trace isn't recorded, and the sampled flag is cleared.
+    Drop,
+    /// The trace is recorded, and the sampled flag is set.
+    RecordAndSample,
+}
+
+impl SamplingDecision {
+    /// Returns true if the sampled flag should be set.
+    pub fn is_sampled(self) -> bool {
+        self == SamplingDecision::RecordAndSample
+    }
+}
+
+/// Decides whether a trace is sampled.
+///
+/// Pass an implementation to constructors such as
+/// [`TraceContext::new_root_with_sampler`](struct.TraceContext.html#method.new_root_with_sampler).
+pub trait Sampler {
+    /// Decide whether the trace with the given trace id is sampled. `parent` is the TraceContext
+    /// the new one continues, or `None` for a root.
+    fn should_sample(&self, trace_id: TraceId, parent: Option<&TraceContext>) -> SamplingDecision;
+}
+
+/// Samples every trace.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AlwaysOn;
+
+impl Sampler for AlwaysOn {
+    fn should_sample(&self, _: TraceId, _: Option<&TraceContext>) -> SamplingDecision {
+        SamplingDecision::RecordAndSample
+    }
+}
+
+/// Samples no trace.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AlwaysOff;
+
+impl Sampler for AlwaysOff {
+    fn should_sample(&self, _: TraceId, _: Option<&TraceContext>) -> SamplingDecision {
+        SamplingDecision::Drop
+    }
+}
+
+/// Samples a fraction of traces, based on the lower 64 bits of the trace id.
+///
+/// Since the decision only depends on the trace id, every service using the same ratio makes
+/// the same decision for a trace.
+///
+/// ## Examples
+/// ```
+/// use trace_context::{Sampler, TraceIdRatio};
+///
+/// let sampler = TraceIdRatio::new(0.25);
+///
+/// assert!(sampler.should_sample(0x1000.into(), None).is_sampled());
+/// assert!(!sampler.should_sample(u128::from(u64::MAX).into(), None).is_sampled());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceIdRatio {
+    ratio: f64,
+    threshold: u128,
+}
+
+impl TraceIdRatio {
+    /// Create a TraceIdRatio sampling the given fraction of traces. The ratio is clamped to
+    /// `0.0..=1.0`; `NaN` samples nothing.
+    pub fn new(ratio: f64) -> Self {
+        let ratio = if ratio.is_nan() {
+            0.0
+        } else {
+            ratio.clamp(0.0, 1.0)
+        };
+        Self {
+            ratio,
+            threshold: (ratio * 2f64.powi(64)) as u128,
+        }
+    }
+
+    /// Return the fraction of traces this sampler samples.
+    pub fn ratio(&self) -> f64 {
+        self.ratio
+    }
+}
+
+impl Sampler for TraceIdRatio {
+    fn should_sample(&self, trace_id: TraceId, _: Option<&TraceContext>) -> SamplingDecision {
+        if u128::from(u128::from(trace_id) as u64) < self.threshold {
+            SamplingDecision::RecordAndSample
+        } else {
+            SamplingDecision::Drop
+        }
+    }
+}
+
+/// Follows the sampled flag of the parent, and delegates to another sampler for roots.
+///
+/// ## Examples
+/// ```
+/// use trace_context::{AlwaysOff, ParentBased, Sampler, TraceContext};
+///
+/// let sampler = ParentBased::new(AlwaysOff);
+/// let parent = TraceContext::new_root();
+///
+/// assert!(sampler.should_sample(parent.trace_id(), Some(&parent)).is_sampled());
+/// assert!(!sampler.should_sample(parent.trace_id(), None).is_sampled());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParentBased<S> {
+    root: S,
+}
+
+impl<S: Sampler> ParentBased<S> {
+    /// Create a ParentBased sampler using `root` for traces without a parent.
+    pub fn new(root: S) -> Self {
+        Self { root }
+    }
+
+    /// Return the sampler used for traces without a parent.
+    pub fn root(&self) -> &S {
+        &self.root
+    }
+}
+
+impl<S: Sampler> Sampler for ParentBased<S> {
+    fn should_sample(&self, trace_id: TraceId, parent: Option<&TraceContext>) -> SamplingDecision {
+        match parent {
+            Some(parent) if parent.sampled() => SamplingDecision::RecordAndSample,
+            Some(_) => SamplingDecision::Drop,
+            None => self.root.should_sample(trace_id, None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AlwaysOff, AlwaysOn, ParentBased, Sampler, SamplingDecision, TraceIdRatio};
+    use crate::{TraceContext, TraceId};
+
+    #[test]
+    fn always() {
+        let trace_id = TraceId::from(1);
+        assert_eq!(
+            AlwaysOn.should_sample(trace_id, None),
+            SamplingDecision::RecordAndSample
+        );
+        assert_eq!(
+            AlwaysOff.should_sample(trace_id, None),
+            SamplingDecision::Drop
+        );
+    }
+
+    #[test]
+    fn ratio() {
+        let half = TraceIdRatio::new(0.5);
+        assert!(half
+            .should_sample(TraceId::from(1 << 64 | 1), None)
+            .is_sampled());
+        assert!(!half
+            .should_sample(TraceId::from(1 << 63), None)
+            .is_sampled());
+
+        let all = TraceIdRatio::new(2.0);
+        assert_eq!(all.ratio(), 1.0);
+        assert!(all
+            .should_sample(TraceId::from(u128::MAX), None)
+            .is_sampled());
+
+        let none = TraceIdRatio::new(f64::NAN);
+        assert_eq!(none.ratio(), 0.0);
+        assert!(!none.should_sample(TraceId::from(1), None).is_sampled());
+
+        let sampled = (0..1000)
+            .filter(|_| {
+                let trace_id = TraceContext::new_root().trace_id();
+                TraceIdRatio::new(0.1)
+                    .should_sample(trace_id, None)
+                    .is_sampled()
+            })
+            .count();
+        assert!(sampled > 50 && sampled < 150, "sampled {}", sampled);
+    }
+
+    #[test]
+    fn parent_based() {
+        let sampler = ParentBased::new(AlwaysOn);
+        let mut parent = TraceContext::new_root();
+        parent.set_sampled(false);
+        assert!(!sampler
+            .should_sample(parent.trace_id(), Some(&parent))
+            .is_sampled());
+        assert!(sampler.should_sample(parent.trace_id(), None).is_sampled());
+    }
+
+    #[test]
+    fn constructors() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        assert!(!TraceContext::new_root_with_sampler(&AlwaysOff).sampled());
+        assert!(TraceContext::new_root_with_sampler(&AlwaysOn).sampled());
+
+        let context = TraceContext::extract_with_sampler(&http::HeaderMap::new(), &AlwaysOff)?;
+        assert!(!context.sampled());
+        assert_eq!(context.parent_id(), None);
+
+        let mut headers = http::HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01".parse()?,
+        );
+        let context = TraceContext::extract_with_sampler(&headers, &AlwaysOff)?;
+        assert!(context.sampled());
+        Ok(())
+    }
+}
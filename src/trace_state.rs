@@ -0,0 +1,408 @@
+use crate::ParseError;
+use std::fmt;
+use std::str::FromStr;
+
+/// The maximum number of list-members allowed in a `tracestate` header.
+pub(crate) const MAX_MEMBERS: usize = 32;
+
+/// List-members longer than this are the first to go when a TraceState is truncated.
+const LARGE_MEMBER_LEN: usize = 128;
+
+/// Vendor-specific trace data carried in the `tracestate` HTTP header.
+///
+/// The list-members are kept in the order they were received, which is also the order they
+/// will be emitted in.
+///
+/// ## Examples
+/// ```
+/// let state: trace_context::TraceState = "rojo=00f067aa0ba902b7,congo=t61rcWkgMzE".parse().unwrap();
+///
+/// assert_eq!(state.to_string(), "rojo=00f067aa0ba902b7,congo=t61rcWkgMzE");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceState {
+    entries: Vec<(String, String)>,
+}
+
+impl TraceState {
+    /// Create an empty TraceState.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if the TraceState doesn't contain any list-members.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Return the number of list-members in the TraceState.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Return the value stored for `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Iterate over the list-members as `(key, value)` pairs, in header order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Insert or update a list-member.
+    ///
+    /// As required by the spec, the list-member is moved to the front of the list. If the list
+    /// grows beyond 32 list-members it's truncated according to the default
+    /// [`TruncationPolicy`](struct.TruncationPolicy.html) rules, never removing the inserted
+    /// list-member.
+    ///
+    /// ## Examples
+    /// ```
+    /// let mut state: trace_context::TraceState = "rojo=1,acme=1".parse().unwrap();
+    ///
+    /// state.insert("acme", "2").unwrap();
+    ///
+    /// assert_eq!(state.to_string(), "acme=2,rojo=1");
+    /// ```
+    pub fn insert(&mut self, key: &str, value: &str) -> Result<(), ParseError> {
+        if !valid_key(key) {
+            return Err(ParseError::InvalidTraceStateKey);
+        }
+        if !valid_value(value) {
+            return Err(ParseError::InvalidTraceStateValue);
+        }
+
+        self.remove(key);
+        self.entries.insert(0, (key.to_owned(), value.to_owned()));
+        self.truncate_from(1, &TruncationPolicy::new().max_len(usize::MAX));
+        Ok(())
+    }
+
+    /// Remove a list-member, returning its value if it was present.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    /// Remove list-members until the TraceState fits within the limits of `policy`.
+    ///
+    /// Following the spec, list-members longer than 128 characters are removed first, after
+    /// which list-members are removed starting from the end of the list.
+    ///
+    /// ## Examples
+    /// ```
+    /// use trace_context::{TraceState, TruncationPolicy};
+    ///
+    /// let mut state: TraceState = "acme=1,rojo=00f067aa0ba902b7,congo=t61rcWkgMzE".parse().unwrap();
+    ///
+    /// state.truncate(&TruncationPolicy::new().max_len(30));
+    ///
+    /// assert_eq!(state.to_string(), "acme=1,rojo=00f067aa0ba902b7");
+    /// ```
+    pub fn truncate(&mut self, policy: &TruncationPolicy) {
+        self.truncate_from(0, policy);
+    }
+
+    /// Truncate like `truncate`, only removing list-members from index `start` onwards.
+    fn truncate_from(&mut self, start: usize, policy: &TruncationPolicy) {
+        while self.entries.len() > start
+            && (self.entries.len() > policy.max_members || self.header_len() > policy.max_len)
+        {
+            let index = self.entries[start..]
+                .iter()
+                .rposition(|(k, v)| k.len() + 1 + v.len() > LARGE_MEMBER_LEN)
+                .map_or(self.entries.len() - 1, |index| start + index);
+            self.entries.remove(index);
+        }
+    }
+
+    /// The length of the TraceState when formatted as a `tracestate` header.
+    fn header_len(&self) -> usize {
+        let members: usize = self
+            .entries
+            .iter()
+            .map(|(k, v)| k.len() + 1 + v.len())
+            .sum();
+        members + self.entries.len().saturating_sub(1)
+    }
+}
+
+/// Limits enforced by [`TraceState::truncate`](struct.TraceState.html#method.truncate).
+///
+/// By default a TraceState is limited to the 32 list-members allowed by the spec and to 512
+/// characters, which is the minimum length the spec requires vendors to propagate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TruncationPolicy {
+    max_members: usize,
+    max_len: usize,
+}
+
+impl TruncationPolicy {
+    /// Create a TruncationPolicy with the default limits.
+    pub fn new() -> Self {
+        Self {
+            max_members: MAX_MEMBERS,
+            max_len: 512,
+        }
+    }
+
+    /// Set the maximum number of list-members. Values above 32 are capped at 32.
+    pub fn max_members(mut self, max_members: usize) -> Self {
+        self.max_members = max_members.min(MAX_MEMBERS);
+        self
+    }
+
+    /// Set the maximum length in characters of the formatted `tracestate` header.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+}
+
+impl Default for TruncationPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromStr for TraceState {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut entries: Vec<(String, String)> = Vec::new();
+
+        for member in s.split(',') {
+            let member = member.trim_matches(|c| c == ' ' || c == '\t');
+            if member.is_empty() {
+                continue;
+            }
+
+            let eq = member.find('=').ok_or(ParseError::InvalidTraceStateValue)?;
+            let (key, value) = (&member[..eq], &member[eq + 1..]);
+            if !valid_key(key) {
+                return Err(ParseError::InvalidTraceStateKey);
+            }
+            if !valid_value(value) {
+                return Err(ParseError::InvalidTraceStateValue);
+            }
+            if entries.iter().any(|(k, _)| k == key) {
+                return Err(ParseError::DuplicateTraceStateKey);
+            }
+            if entries.len() == MAX_MEMBERS {
+                return Err(ParseError::TooManyTraceStateMembers);
+            }
+
+            entries.push((key.to_owned(), value.to_owned()));
+        }
+
+        Ok(Self { entries })
+    }
+}
+
+impl fmt::Display for TraceState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (key, value)) in self.entries.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns true if `key` is a valid simple or multi-tenant (`tenant@system`) key.
+fn valid_key(key: &str) -> bool {
+    match key.find('@') {
+        None => key_part(key, 256, |b| b.is_ascii_lowercase()),
+        Some(at) => {
+            let (tenant, system) = (&key[..at], &key[at + 1..]);
+            key_part(tenant, 241, |b| {
+                b.is_ascii_lowercase() || b.is_ascii_digit()
+            }) && key_part(system, 14, |b| b.is_ascii_lowercase())
+        }
+    }
+}
+
+fn key_part(part: &str, max_len: usize, first: impl Fn(u8) -> bool) -> bool {
+    let bytes = part.as_bytes();
+    match bytes.split_first() {
+        Some((&head, tail)) if bytes.len() <= max_len && first(head) => tail
+            .iter()
+            .all(|&b| b.is_ascii_lowercase() || b.is_ascii_digit() || b"_-*/".contains(&b)),
+        _ => false,
+    }
+}
+
+/// Returns true if `value` is a valid list-member value.
+fn valid_value(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    match bytes.last() {
+        Some(&last) if bytes.len() <= 256 && last != b' ' => bytes
+            .iter()
+            .all(|&b| (0x20..=0x7e).contains(&b) && b != b',' && b != b'='),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TraceState, TruncationPolicy};
+    use crate::ParseError;
+
+    #[test]
+    fn parse() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let state: TraceState = "rojo=00f067aa0ba902b7, congo=t61rcWkgMzE".parse()?;
+        assert_eq!(state.len(), 2);
+        assert_eq!(state.to_string(), "rojo=00f067aa0ba902b7,congo=t61rcWkgMzE");
+        Ok(())
+    }
+
+    #[test]
+    fn empty_members() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let state: TraceState = " ,acme=1,\t,".parse()?;
+        assert_eq!(state.to_string(), "acme=1");
+        assert!("".parse::<TraceState>()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn multi_tenant_key() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let state: TraceState = "fw529a3039@dt=d4cda95b652f4a15".parse()?;
+        assert_eq!(state.to_string(), "fw529a3039@dt=d4cda95b652f4a15");
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_key() {
+        for key in &[
+            "Acme",
+            "1acme",
+            "acme@",
+            "@dt",
+            "acme@1dt",
+            "acme@systemnameistoolong",
+        ] {
+            assert_eq!(
+                format!("{}=1", key).parse::<TraceState>().unwrap_err(),
+                ParseError::InvalidTraceStateKey
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_value() {
+        for member in &["acme", "acme=", "acme=a=b", "acme=a\u{7f}"] {
+            assert_eq!(
+                member.parse::<TraceState>().unwrap_err(),
+                ParseError::InvalidTraceStateValue
+            );
+        }
+    }
+
+    #[test]
+    fn duplicate_key() {
+        assert_eq!(
+            "acme=1,acme=2".parse::<TraceState>().unwrap_err(),
+            ParseError::DuplicateTraceStateKey
+        );
+    }
+
+    #[test]
+    fn too_many_members() {
+        let members: Vec<String> = (0..33).map(|i| format!("k{}=v", i)).collect();
+        assert_eq!(
+            members.join(",").parse::<TraceState>().unwrap_err(),
+            ParseError::TooManyTraceStateMembers
+        );
+    }
+
+    #[test]
+    fn insert_moves_to_front() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut state: TraceState = "rojo=1,congo=2".parse()?;
+        state.insert("acme", "a")?;
+        assert_eq!(state.to_string(), "acme=a,rojo=1,congo=2");
+        state.insert("congo", "3")?;
+        assert_eq!(state.to_string(), "congo=3,acme=a,rojo=1");
+        assert_eq!(state.get("congo"), Some("3"));
+        assert_eq!(state.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn insert_invalid() {
+        let mut state = TraceState::new();
+        assert_eq!(
+            state.insert("Acme", "1").unwrap_err(),
+            ParseError::InvalidTraceStateKey
+        );
+        assert_eq!(
+            state.insert("acme", "a,b").unwrap_err(),
+            ParseError::InvalidTraceStateValue
+        );
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn insert_caps_members() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let members: Vec<String> = (0..32).map(|i| format!("k{}=v", i)).collect();
+        let mut state: TraceState = members.join(",").parse()?;
+        state.insert("acme", "1")?;
+        assert_eq!(state.len(), 32);
+        assert_eq!(state.iter().next(), Some(("acme", "1")));
+        assert_eq!(state.get("k30"), Some("v"));
+        assert_eq!(state.get("k31"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn insert_keeps_large_member() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>>
+    {
+        let members: Vec<String> = (0..32).map(|i| format!("k{}=v", i)).collect();
+        let mut state: TraceState = members.join(",").parse()?;
+        let large = "x".repeat(200);
+        state.insert("acme", &large)?;
+        assert_eq!(state.len(), 32);
+        assert_eq!(state.get("acme"), Some(large.as_str()));
+        assert_eq!(state.get("k31"), None);
+
+        state.insert("rojo", "1")?;
+        assert_eq!(state.len(), 32);
+        assert_eq!(state.get("acme"), None);
+        assert_eq!(state.get("k30"), Some("v"));
+        Ok(())
+    }
+
+    #[test]
+    fn remove() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut state: TraceState = "rojo=1,acme=2".parse()?;
+        assert_eq!(state.remove("acme"), Some("2".to_owned()));
+        assert_eq!(state.remove("acme"), None);
+        assert_eq!(state.to_string(), "rojo=1");
+        Ok(())
+    }
+
+    #[test]
+    fn iter() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let state: TraceState = "rojo=1,acme=2".parse()?;
+        let members: Vec<(&str, &str)> = state.iter().collect();
+        assert_eq!(members, vec![("rojo", "1"), ("acme", "2")]);
+        Ok(())
+    }
+
+    #[test]
+    fn truncate_large_members_first(
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let large = "x".repeat(130);
+        let mut state: TraceState = format!("a=1,big={},b=2,c=3", large).parse()?;
+        state.truncate(&TruncationPolicy::new().max_len(20));
+        assert_eq!(state.to_string(), "a=1,b=2,c=3");
+        state.truncate(&TruncationPolicy::new().max_members(2));
+        assert_eq!(state.to_string(), "a=1,b=2");
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+/// Vendor-specific data from inbound headers that has no place in the W3C trace context, kept so
+/// the matching propagator can write it back.
+///
+/// It's passed on to children of the TraceContext. See
+/// [`TraceContext::vendor_fields`](struct.TraceContext.html#method.vendor_fields).
+///
+/// ## Examples
+/// ```
+/// let mut carrier = std::collections::HashMap::new();
+/// carrier.insert("x-datadog-trace-id".to_string(), "1234".to_string());
+/// carrier.insert("x-datadog-parent-id".to_string(), "5678".to_string());
+/// carrier.insert("x-datadog-origin".to_string(), "synthetics".to_string());
+///
+/// let context = trace_context::DatadogPropagator::new().extract(&carrier).unwrap().unwrap();
+///
+/// assert_eq!(context.vendor_fields().datadog_origin(), Some("synthetics"));
+/// assert!(context.vendor_fields().xray_fields().is_empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct VendorFields {
+    pub(crate) xray_fields: Vec<(String, String)>,
+    pub(crate) datadog_origin: Option<String>,
+    pub(crate) datadog_priority: Option<i8>,
+    pub(crate) datadog_tags: Vec<(String, String)>,
+}
+
+impl VendorFields {
+    /// Return the `key=value` pairs of an inbound `X-Amzn-Trace-Id` header that aren't part of
+    /// the trace context itself, such as `Lineage`.
+    ///
+    /// They're written back by
+    /// [`XRayPropagator::inject`](struct.XRayPropagator.html#method.inject).
+    pub fn xray_fields(&self) -> &[(String, String)] {
+        &self.xray_fields
+    }
+
+    /// Return the origin of the trace from an inbound `x-datadog-origin` header, such as
+    /// `synthetics`.
+    ///
+    /// It's written back by
+    /// [`DatadogPropagator::inject`](struct.DatadogPropagator.html#method.inject).
+    pub fn datadog_origin(&self) -> Option<&str> {
+        self.datadog_origin.as_deref()
+    }
+
+    /// Return the sampling priority of an inbound `x-datadog-sampling-priority` header, such as
+    /// `2` for a manual keep.
+    ///
+    /// It's written back by
+    /// [`DatadogPropagator::inject`](struct.DatadogPropagator.html#method.inject), unless the
+    /// sampled flag no longer agrees with it.
+    pub fn datadog_sampling_priority(&self) -> Option<i8> {
+        self.datadog_priority
+    }
+
+    /// Return the propagated `_dd.p.*` tags of an inbound `x-datadog-tags` header, except for
+    /// `_dd.p.tid` which is part of the trace id.
+    ///
+    /// They're written back by
+    /// [`DatadogPropagator::inject`](struct.DatadogPropagator.html#method.inject).
+    pub fn datadog_tags(&self) -> &[(String, String)] {
+        &self.datadog_tags
+    }
+}
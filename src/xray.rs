@@ -0,0 +1,336 @@
+use crate::{
+    hex_field, Deferral, Extractor, IdGenerator, Injector, ParseError, Propagator,
+    RandomIdGenerator, SpanId, TraceContext, TraceId,
+};
+
+const HEADER: &str = "x-amzn-trace-id";
+const ROOT_VERSION: &str = "1";
+
+/// Converts between the [AWS X-Ray](https://docs.aws.amazon.com/xray/latest/devguide/xray-concepts.html#xray-concepts-tracingheader)
+/// `X-Amzn-Trace-Id` header and TraceContext.
+///
+/// The X-Ray root `1-{epoch}-{random}` maps onto the 128-bit trace id by concatenating the
+/// 8 hex character epoch and the 24 hex character random part.
+///
+/// ## Examples
+/// ```
+/// use trace_context::XRayPropagator;
+///
+/// let mut headers = http::HeaderMap::new();
+/// headers.insert(
+///     "x-amzn-trace-id",
+///     "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1;Lineage=a87bd80c:0"
+///         .parse()
+///         .unwrap(),
+/// );
+///
+/// let context = XRayPropagator::new().extract(&headers).unwrap().unwrap();
+///
+/// assert_eq!(context.trace_id().to_string(), "5759e988bd862e3fe1be46a994272793");
+/// assert_eq!(context.parent_id().unwrap().to_string(), "53995c3f42cd8ad8");
+/// assert!(context.sampled());
+/// assert_eq!(context.vendor_fields().xray_fields(), &[("Lineage".to_string(), "a87bd80c:0".to_string())][..]);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct XRayPropagator;
+
+impl XRayPropagator {
+    /// Create an XRayPropagator.
+    pub fn new() -> Self {
+        XRayPropagator
+    }
+
+    /// Create and return a TraceContext based on the `X-Amzn-Trace-Id` header of the carrier.
+    ///
+    /// A header without `Parent`, as sent by load balancers, results in a root TraceContext
+    /// using the given trace id. A header with `Sampled=?` or without `Sampled` results in an
+    /// unsampled TraceContext whose decision is
+    /// [deferred](struct.TraceContext.html#method.sampling_deferred). Unknown `key=value` pairs
+    /// are kept, see [`VendorFields::xray_fields`](struct.VendorFields.html#method.xray_fields).
+    /// Returns `Ok(None)` if the carrier has no `X-Amzn-Trace-Id` header.
+    pub fn extract<E: Extractor + ?Sized>(
+        &self,
+        carrier: &E,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        self.extract_with(carrier, &RandomIdGenerator::new())
+    }
+
+    /// Create and return a TraceContext like [`extract`](#method.extract), using `gen` to
+    /// generate its id.
+    pub fn extract_with<E: Extractor + ?Sized, G: IdGenerator + ?Sized>(
+        &self,
+        carrier: &E,
+        gen: &G,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        let header = match carrier.get(HEADER) {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+
+        let mut trace_id = None;
+        let mut parent_id = None;
+        let mut sampled = None;
+        let mut fields = Vec::new();
+
+        for pair in header.split(';') {
+            let pair = pair.trim();
+            let eq = match pair.find('=') {
+                Some(eq) => eq,
+                None => continue,
+            };
+            let (key, value) = (&pair[..eq], &pair[eq + 1..]);
+            match key {
+                "Root" => trace_id = Some(parse_root(value)?),
+                "Parent" => parent_id = Some(parse_parent(value)?),
+                "Sampled" => sampled = Some(parse_sampled(value)?),
+                _ => fields.push((key.to_owned(), value.to_owned())),
+            }
+        }
+
+        let trace_id = trace_id.ok_or(ParseError::MissingField("Root"))?;
+        let mut context = match parent_id {
+            Some(parent_id) => TraceContext::remote(gen, trace_id, parent_id, Default::default()),
+            None => TraceContext::remote_root(gen, trace_id),
+        };
+        match sampled {
+            Some(Some(sampled)) => context.set_sampled(sampled),
+            Some(None) => context.defer_sampling(Deferral::Explicit),
+            None => context.defer_sampling(Deferral::Absent),
+        }
+        context.vendor.xray_fields = fields;
+
+        Ok(Some(context))
+    }
+
+    /// Add the `X-Amzn-Trace-Id` header for the TraceContext to the carrier.
+    ///
+    /// A deferred sampling decision is written back the way it was received, as `Sampled=?` or
+    /// by omitting `Sampled`.
+    ///
+    /// ## Examples
+    /// ```
+    /// let context = trace_context::TraceContext::from_bytes(
+    ///     b"00-5759e988bd862e3fe1be46a994272793-53995c3f42cd8ad8-01"
+    /// ).unwrap();
+    ///
+    /// let mut headers = http::HeaderMap::new();
+    /// trace_context::XRayPropagator::new().inject(&context, &mut headers);
+    ///
+    /// assert_eq!(
+    ///     headers.get("x-amzn-trace-id").unwrap(),
+    ///     &format!("Root=1-5759e988-bd862e3fe1be46a994272793;Parent={};Sampled=1", context.id()),
+    /// );
+    /// ```
+    pub fn inject<I: Injector + ?Sized>(&self, context: &TraceContext, carrier: &mut I) {
+        let trace_id = context.trace_id().to_string();
+        let mut header = format!(
+            "Root={}-{}-{};Parent={}",
+            ROOT_VERSION,
+            &trace_id[..8],
+            &trace_id[8..],
+            context.id(),
+        );
+        match context.deferred {
+            Some(Deferral::Absent) => {}
+            Some(Deferral::Explicit) => header.push_str(";Sampled=?"),
+            None if context.sampled() => header.push_str(";Sampled=1"),
+            None => header.push_str(";Sampled=0"),
+        }
+        for (key, value) in context.vendor_fields().xray_fields() {
+            header.push(';');
+            header.push_str(key);
+            header.push('=');
+            header.push_str(value);
+        }
+        carrier.set(HEADER, header);
+    }
+}
+
+impl Propagator for XRayPropagator {
+    fn name(&self) -> &'static str {
+        "xray"
+    }
+
+    fn extract_with(
+        &self,
+        carrier: &dyn Extractor,
+        gen: &dyn IdGenerator,
+    ) -> Result<Option<TraceContext>, ParseError> {
+        XRayPropagator::extract_with(self, carrier, gen)
+    }
+
+    fn inject(&self, context: &TraceContext, carrier: &mut dyn Injector) {
+        XRayPropagator::inject(self, context, carrier)
+    }
+}
+
+/// Parse a `1-{8 hex epoch}-{24 hex random}` root into a 128-bit trace id.
+fn parse_root(root: &str) -> Result<TraceId, ParseError> {
+    let mut parts = root.split('-');
+    if parts.next() != Some(ROOT_VERSION) {
+        return Err(ParseError::InvalidVersion);
+    }
+    let epoch = hex_field(parts.next().map(str::as_bytes), "Root", 8)?;
+    let random = hex_field(parts.next().map(str::as_bytes), "Root", 24)?;
+    if parts.next().is_some() {
+        return Err(ParseError::BadLength("Root"));
+    }
+
+    let trace_id = TraceId::from(epoch << 96 | random);
+    if !trace_id.is_valid() {
+        return Err(ParseError::ZeroTraceId);
+    }
+    Ok(trace_id)
+}
+
+fn parse_parent(parent: &str) -> Result<SpanId, ParseError> {
+    let parent_id = SpanId::from(hex_field(Some(parent.as_bytes()), "Parent", 16)? as u64);
+    if !parent_id.is_valid() {
+        return Err(ParseError::ZeroParentId);
+    }
+    Ok(parent_id)
+}
+
+/// Parse the sampling decision, where `?` means the decision is left to the receiver.
+fn parse_sampled(sampled: &str) -> Result<Option<bool>, ParseError> {
+    match sampled {
+        "1" => Ok(Some(true)),
+        "0" => Ok(Some(false)),
+        "?" => Ok(None),
+        _ => Err(ParseError::InvalidSamplingState),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{XRayPropagator, HEADER};
+    use crate::carrier::test::carrier;
+    use crate::ParseError;
+    use std::collections::HashMap;
+
+    #[test]
+    fn extract() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let context = XRayPropagator::new()
+            .extract(&carrier(&[(
+                HEADER,
+                "Root=1-5759e988-bd862e3fe1be46a994272793; Parent=53995c3f42cd8ad8; Sampled=0",
+            )]))?
+            .unwrap();
+        assert_eq!(
+            context.trace_id(),
+            "5759e988bd862e3fe1be46a994272793".parse()?
+        );
+        assert_eq!(context.parent_id(), Some("53995c3f42cd8ad8".parse()?));
+        assert!(!context.sampled());
+        assert!(context.vendor_fields().xray_fields().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn root_only() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let context = XRayPropagator::new()
+            .extract(&carrier(&[(
+                HEADER,
+                "Self=1-67891234-12456789abcdef012345678;Root=1-5759e988-bd862e3fe1be46a994272793",
+            )]))?
+            .unwrap();
+        assert_eq!(
+            context.trace_id(),
+            "5759e988bd862e3fe1be46a994272793".parse()?
+        );
+        assert_eq!(context.parent_id(), None);
+        assert!(context.sampling_deferred());
+        assert!(!context.sampled());
+        assert_eq!(context.vendor_fields().xray_fields()[0].0, "Self");
+
+        let mut carrier = HashMap::new();
+        XRayPropagator::new().inject(&context, &mut carrier);
+        assert_eq!(
+            carrier["x-amzn-trace-id"],
+            format!(
+                "Root=1-5759e988-bd862e3fe1be46a994272793;Parent={};Self=1-67891234-12456789abcdef012345678",
+                context.id()
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deferred_sampling() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let context = XRayPropagator::new()
+            .extract(&carrier(&[(
+                HEADER,
+                "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=?",
+            )]))?
+            .unwrap();
+        assert!(context.sampling_deferred());
+        assert!(!context.sampled());
+
+        let child = context.child();
+        let mut carrier = HashMap::new();
+        XRayPropagator::new().inject(&child, &mut carrier);
+        assert!(carrier["x-amzn-trace-id"].ends_with(";Sampled=?"));
+
+        let mut decided = XRayPropagator::new().extract(&carrier)?.unwrap();
+        decided.set_sampled(true);
+        XRayPropagator::new().inject(&decided, &mut carrier);
+        assert!(carrier["x-amzn-trace-id"].ends_with(";Sampled=1"));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        let xray = XRayPropagator::new();
+        assert_eq!(
+            xray.extract(&carrier(&[(HEADER, "Parent=53995c3f42cd8ad8")]))
+                .unwrap_err(),
+            ParseError::MissingField("Root")
+        );
+        assert_eq!(
+            xray.extract(&carrier(&[(
+                HEADER,
+                "Root=2-5759e988-bd862e3fe1be46a994272793"
+            )]))
+            .unwrap_err(),
+            ParseError::InvalidVersion
+        );
+        assert_eq!(
+            xray.extract(&carrier(&[(
+                HEADER,
+                "Root=1-5759e988-bd862e3fe1be46a99427"
+            )]))
+            .unwrap_err(),
+            ParseError::BadLength("Root")
+        );
+        assert_eq!(
+            xray.extract(&carrier(&[(
+                HEADER,
+                "Root=1-5759e988-bd862e3fe1be46a994272793;Sampled=yes"
+            )]))
+            .unwrap_err(),
+            ParseError::InvalidSamplingState
+        );
+    }
+
+    #[test]
+    fn inject_preserves_fields() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let parent = XRayPropagator::new()
+            .extract(&carrier(&[(HEADER, "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1;Lineage=a87bd80c:0")]))?
+            .unwrap();
+        let child = parent.child();
+        let mut carrier = HashMap::new();
+        XRayPropagator::new().inject(&child, &mut carrier);
+        assert_eq!(
+            carrier["x-amzn-trace-id"],
+            format!(
+                "Root=1-5759e988-bd862e3fe1be46a994272793;Parent={};Sampled=1;Lineage=a87bd80c:0",
+                child.id()
+            )
+        );
+
+        let extracted = XRayPropagator::new().extract(&carrier)?.unwrap();
+        assert_eq!(extracted.trace_id(), parent.trace_id());
+        assert_eq!(extracted.parent_id(), Some(child.id()));
+        Ok(())
+    }
+}